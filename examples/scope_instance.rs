@@ -32,7 +32,7 @@ fn main() -> Result<(), Error> {
         println!("Serial: {serial}");
     }
 
-    let task = session.read_async(256, std::time::Duration::from_secs(1))?;
+    let task = session.read_async(256)?;
     task.terminate()?;
 
     Ok(())
@@ -0,0 +1,391 @@
+//! Asynchronous reads and writes
+//!
+//! `Session::read_async` and `Session::write_async` start a `viReadAsync` / `viWriteAsync` job and return an
+//! `AsyncTask`, a future resolving to an `AsyncResult` once VISA reports the `IoCompletion` event.
+//!
+//! The buffers are owned by the task, and stay alive until VISA is done with them - even if the task is dropped early.
+//! The session timeout (`TmoValue`) applies to the jobs.
+//!
+//! The completion is delivered through a handler installed on the session, so `Event::IoCompletion` must not be
+//! enabled for queuing on a session used for asynchronous I/O.
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{bindings, error::Error, event, Session};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    task::{ready, Context, Poll, Waker},
+};
+
+//------------ Completion registry -------------------------------------
+
+type JobKey = (bindings::ViSession, bindings::ViJobId);
+type Orphan = Box<dyn std::fmt::Debug + Send>;
+
+/// The state of a job, shared between its task and the completion handler
+#[derive(Debug, Default)]
+struct Job {
+    completion: Option<(bindings::ViStatus, usize)>,
+    waker: Option<Waker>,
+
+    /// The buffer of a task dropped before completion, freed once VISA is done with it
+    orphaned: Option<Orphan>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    jobs: HashMap<JobKey, Job>,
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Release the state kept for a session once it is closed
+///
+/// Closing a session aborts its jobs, so the buffers of orphaned tasks can be freed.
+pub(crate) fn forget_session(session: bindings::ViSession) {
+    registry().jobs.retain(|(vi, _), _| *vi != session);
+}
+
+/// Poll a job for its completion status and count, registering the waker if it is still running
+pub(crate) fn poll_job(
+    session: bindings::ViSession,
+    job_id: bindings::ViJobId,
+    cx: &mut Context<'_>,
+) -> Poll<(bindings::ViStatus, usize)> {
+    let mut registry = registry();
+    let key = (session, job_id);
+    let job = registry.jobs.entry(key).or_default();
+    let Some(completion) = job.completion else {
+        job.waker = Some(cx.waker().clone());
+        return Poll::Pending;
+    };
+    registry.jobs.remove(&key);
+    Poll::Ready(completion)
+}
+
+/// Terminate a job, handing its buffer over to the registry until VISA is done with it
+pub(crate) fn terminate_job(
+    session: bindings::ViSession,
+    job_id: bindings::ViJobId,
+    buffer: Orphan,
+) -> Result<(), Error> {
+    {
+        let mut registry = registry();
+        let key = (session, job_id);
+        let job = registry.jobs.entry(key).or_default();
+        if job.completion.is_some() {
+            registry.jobs.remove(&key);
+            return Ok(());
+        }
+        job.waker = None;
+        job.orphaned = Some(buffer);
+    }
+
+    Error::wrap_binding(Some(session), || unsafe {
+        bindings::viTerminate(session, bindings::VI_NULL as u16, job_id)
+    })
+}
+
+/// Record the completion of a job, waking its task or freeing its orphaned buffer
+pub(crate) fn complete_job(
+    session: bindings::ViSession,
+    job_id: bindings::ViJobId,
+    status: bindings::ViStatus,
+    count: usize,
+) {
+    let waker = {
+        let mut registry = registry();
+        let key = (session, job_id);
+        let job = registry.jobs.entry(key).or_default();
+        if job.orphaned.is_some() {
+            registry.jobs.remove(&key);
+            None
+        } else {
+            job.completion = Some((status, count));
+            job.waker.take()
+        }
+    };
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Handler for `Event::IoCompletion`, recording the result of a job and waking its task
+struct CompletionHandler;
+impl event::Handler for CompletionHandler {
+    fn handle(session: bindings::ViSession, event: &event::EventContext) -> Result<(), Error> {
+        let job_id = event.job_id()?;
+        let status = event.status()?;
+        let count = event.ret_count()?;
+        complete_job(session, job_id, status, count);
+        Ok(())
+    }
+}
+
+//------------ Tasks ---------------------------------------------------
+
+/// The result of an asynchronous job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsyncResult {
+    /// The success code of the job, such as `VI_SUCCESS`, `VI_SUCCESS_TERM_CHAR` or `VI_SUCCESS_MAX_CNT`
+    pub status: bindings::ViStatus,
+
+    /// The number of bytes transferred (`VI_ATTR_RET_COUNT`)
+    pub count: usize,
+
+    /// The buffer of the job
+    ///
+    /// For reads, the data received, truncated to `count`. For writes, the data that was passed in.
+    pub data: Vec<u8>,
+}
+
+/// An asynchronous job that can be awaited or terminated
+///
+/// Dropping the task before it completes terminates the job.
+#[derive(Debug)]
+#[must_use = "Dropping the task terminates the job"]
+pub struct AsyncTask {
+    session: Session,
+    job_id: bindings::ViJobId,
+    buffer: Option<Vec<u8>>,
+    read: bool,
+    finished: bool,
+}
+impl AsyncTask {
+    /// The VISA job id of the task
+    #[must_use]
+    pub fn job_id(&self) -> bindings::ViJobId {
+        self.job_id
+    }
+
+    /// Terminate the job before it completes
+    ///
+    /// # Errors
+    /// Will return an error if the job cannot be terminated
+    pub fn terminate(mut self) -> Result<(), Error> {
+        self.cancel()
+    }
+
+    /// Block the current thread until the job completes
+    ///
+    /// # Errors
+    /// Will return an error if the job fails
+    pub fn wait(mut self) -> Result<AsyncResult, Error> {
+        block_on(&mut self)
+    }
+
+    /// Terminate the job, and hand its buffer over to the registry until VISA is done with it
+    fn cancel(&mut self) -> Result<(), Error> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        terminate_job(
+            self.session.session_id(),
+            self.job_id,
+            Box::new(self.buffer.take().unwrap_or_default()),
+        )
+    }
+}
+impl Future for AsyncTask {
+    type Output = Result<AsyncResult, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(Error::from_msg("Async task polled after completion")));
+        }
+
+        let vi = this.session.session_id();
+        let (status, count) = ready!(poll_job(vi, this.job_id, cx));
+        this.finished = true;
+        if status < 0 {
+            return Poll::Ready(Err(Error::new(status, Some(vi))));
+        }
+
+        let mut data = this.buffer.take().unwrap_or_default();
+        if this.read {
+            data.truncate(count);
+        }
+        Poll::Ready(Ok(AsyncResult {
+            status,
+            count,
+            data,
+        }))
+    }
+}
+impl Drop for AsyncTask {
+    fn drop(&mut self) {
+        self.cancel().ok();
+    }
+}
+
+/// Wakes a thread blocked in `block_on`
+struct ThreadWaker(std::thread::Thread);
+impl std::task::Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Block the current thread until a job future completes
+pub(crate) fn block_on<F: Future + Unpin>(future: &mut F) -> F::Output {
+    let thread = std::thread::current();
+    let waker = Waker::from(Arc::new(ThreadWaker(thread)));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(result) = Pin::new(&mut *future).poll(&mut context) {
+            return result;
+        }
+        std::thread::park();
+    }
+}
+
+impl Session {
+    /// Install the completion handler on the session, once
+    pub(crate) fn enable_async_io(&self) -> Result<(), Error> {
+        let vi = self.session_id();
+
+        // Held until the handler is installed, so that clones racing here install it only once.
+        // The completion handler never takes it, unlike the registry lock.
+        let mut installed = self.async_io_installed();
+        if *installed {
+            return Ok(());
+        }
+
+        // Installed without data for the lifetime of the session, which uninstalls it when closed
+        let event = event::Event::IoCompletion as u32;
+        let handler = <CompletionHandler as event::HandlerWithData>::into();
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viInstallHandler(vi, event, handler, std::ptr::null_mut())
+        })?;
+        if let Err(e) = self.enable_event(
+            event::Event::IoCompletion,
+            event::HandlingMechanism::Handler,
+            bindings::VI_NULL,
+        ) {
+            unsafe { bindings::viUninstallHandler(vi, event, handler, std::ptr::null_mut()) };
+            return Err(e);
+        }
+        *installed = true;
+        Ok(())
+    }
+
+    fn start_async(
+        &self,
+        buffer: Vec<u8>,
+        read: bool,
+        start: impl FnOnce(&mut Vec<u8>, &mut bindings::ViJobId) -> bindings::ViStatus,
+    ) -> Result<AsyncTask, Error> {
+        self.enable_async_io()?;
+
+        let mut buffer = buffer;
+        let mut job_id = 0;
+        Error::wrap_binding(Some(self.session_id()), || start(&mut buffer, &mut job_id))?;
+
+        Ok(AsyncTask {
+            session: self.clone(),
+            job_id,
+            buffer: Some(buffer),
+            read,
+            finished: false,
+        })
+    }
+
+    /// Start an asynchronous read of up to `bytes` bytes
+    ///
+    /// The read ends like a synchronous read: on END, the termination character, or once `bytes` bytes are received.
+    ///
+    /// # Errors
+    /// Will return an error if the read cannot be started
+    pub fn read_async(&self, bytes: usize) -> Result<AsyncTask, Error> {
+        let vi = self.session_id();
+        let count = u32::try_from(bytes).map_err(|_| Error::from_msg("Read size too large"))?;
+        self.start_async(vec![0; bytes], true, |buffer, job_id| unsafe {
+            bindings::viReadAsync(vi, buffer.as_mut_ptr(), count, job_id)
+        })
+    }
+
+    /// Start an asynchronous write of `data`
+    ///
+    /// The data is returned in the `AsyncResult` once the write completes.
+    ///
+    /// # Errors
+    /// Will return an error if the write cannot be started
+    pub fn write_async(&self, data: impl Into<Vec<u8>>) -> Result<AsyncTask, Error> {
+        let vi = self.session_id();
+        let data = data.into();
+        let count =
+            u32::try_from(data.len()).map_err(|_| Error::from_msg("Write size too large"))?;
+        self.start_async(data, false, |buffer, job_id| unsafe {
+            bindings::viWriteAsync(vi, buffer.as_ptr(), count, job_id)
+        })
+    }
+}
+
+#[cfg(test)]
+impl AsyncTask {
+    /// A task for a job that was never started, to be completed with `complete_job`
+    pub(crate) fn unstarted(
+        session: Session,
+        job_id: bindings::ViJobId,
+        buffer: Vec<u8>,
+        read: bool,
+    ) -> Self {
+        Self {
+            session,
+            job_id,
+            buffer: Some(buffer),
+            read,
+            finished: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+
+    #[test]
+    fn test_completion_registry() {
+        let mut task = AsyncTask::unstarted(Session::null(), 1, vec![1, 2, 3, 4], true);
+
+        let mut context = Context::from_waker(Waker::noop());
+        assert!(Pin::new(&mut task).poll(&mut context).is_pending());
+        let key = (bindings::VI_NULL, 1);
+        assert!(registry().jobs[&key].waker.is_some());
+
+        registry().jobs.get_mut(&key).unwrap().completion = Some((0, 2));
+        let Poll::Ready(result) = Pin::new(&mut task).poll(&mut context) else {
+            panic!("Task not completed");
+        };
+        assert_eq!(result.unwrap().data, [1, 2]);
+        assert!(!registry().jobs.contains_key(&key));
+    }
+
+    #[test]
+    fn test_async_query() {
+        let session = get_local_device();
+
+        let result = session.write_async("*IDN?\n").unwrap().wait().unwrap();
+        assert_eq!(result.count, 6);
+        assert_eq!(result.data, b"*IDN?\n");
+
+        let result = session.read_async(256).unwrap().wait().unwrap();
+        assert_eq!(result.count, result.data.len());
+        assert!(!result.data.is_empty());
+    }
+}
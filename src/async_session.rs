@@ -0,0 +1,604 @@
+//! Integration with async runtimes
+//!
+//! Enabled with the `tokio` or `futures` features. `AsyncSession` wraps a session for use from async code:
+//! ```ignore
+//! let mut session = AsyncSession::new(session);
+//! let id: String = session.query("*IDN?").await?;
+//!
+//! session.write_all(b"MEAS:VOLT?\n").await?;
+//! let reading = session.read_message().await?;
+//! ```
+//!
+//! Reads, writes and queries are built on `viReadAsync` / `viWriteAsync`, so they never occupy a thread.
+//! Operations without an asynchronous VISA equivalent (status byte, event queue, locking) run on tokio's
+//! blocking thread pool when called from a tokio runtime, or on a dedicated thread otherwise.
+//!
+//! With the `tokio` feature, `AsyncSession` implements `tokio::io::AsyncRead` and `AsyncWrite`.
+//! With the `futures` feature, it implements `futures::io::AsyncRead` and `AsyncWrite`, usable from any executor.
+//!
+//! Dropping a future cancels the operation: asynchronous jobs are terminated, and blocking operations stop at
+//! the next poll interval. A lock acquired by a cancelled `lock` is released.
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{
+    bindings,
+    error::{Error, ErrorType},
+    event,
+    ieee4882::StatusByte,
+    AsyncTask, Session,
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    task::{ready, Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// A session driven from async code
+///
+/// Implements the tokio and futures `AsyncRead` and `AsyncWrite` traits, depending on the enabled features, on
+/// top of the VISA asynchronous operations.
+/// Only one read and one write are in flight at a time. A write completes in the background: the next write, read or
+/// flush waits for it, and reports its errors.
+#[derive(Debug)]
+pub struct AsyncSession {
+    session: Session,
+    chunk_size: usize,
+
+    read: Option<AsyncTask>,
+    pending: Vec<u8>,
+    message_end: bool,
+
+    write: Option<AsyncTask>,
+}
+
+impl AsyncSession {
+    /// Size of the asynchronous reads used by `AsyncRead` and `read_message`
+    pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+    /// Interval at which blocking operations check whether they were cancelled
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Wrap a session for use from async code
+    #[must_use]
+    pub fn new(session: Session) -> Self {
+        Self::with_chunk_size(session, Self::DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Wrap a session, reading from the device in chunks of the given size
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is zero
+    #[must_use]
+    pub fn with_chunk_size(session: Session, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunk size must not be zero");
+        Self {
+            session,
+            chunk_size,
+            read: None,
+            pending: vec![],
+            message_end: true,
+            write: None,
+        }
+    }
+
+    /// Get the underlying session
+    #[must_use]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Returns true if the last read completed a message (END or termination character received)
+    #[must_use]
+    pub fn at_message_end(&self) -> bool {
+        self.message_end
+    }
+
+    /// Return the underlying session
+    ///
+    /// Operations still in flight are terminated, including writes that were not flushed, and data received but not
+    /// yet read is discarded
+    #[must_use]
+    pub fn into_inner(self) -> Session {
+        self.session
+    }
+
+    /// Read the remainder of the current message, up to END or the termination character
+    ///
+    /// Data already received by `AsyncRead` is returned first.
+    ///
+    /// # Errors
+    /// Will return an error if the read fails or times out
+    pub async fn read_message(&mut self) -> Result<Vec<u8>, Error> {
+        // The data received stays in `pending` until the message is complete, so that cancelling loses none of it
+        loop {
+            if let Some(task) = &mut self.read {
+                let result = task.await;
+                self.read = None;
+                let data = self.complete_read(result?);
+                self.pending.extend(data);
+            } else if !self.pending.is_empty() && self.message_end {
+                return Ok(std::mem::take(&mut self.pending));
+            } else {
+                if let Some(task) = &mut self.write {
+                    let result = task.await;
+                    self.write = None;
+                    result?;
+                }
+                self.read = Some(self.session.read_async(self.chunk_size)?);
+            }
+        }
+    }
+
+    /// Read the remainder of the current message as a string, using the session text options
+    ///
+    /// # Errors
+    /// Will return an error if the read fails, or the data cannot be decoded
+    pub async fn read_string(&mut self) -> Result<String, Error> {
+        let message = self.read_message().await?;
+        self.session.text().decode_message(&message)
+    }
+
+    /// Write a string to the session, using the session text options
+    ///
+    /// # Errors
+    /// Will return an error if the data cannot be encoded or written
+    pub async fn write_string(&mut self, buf: &str) -> Result<(), Error> {
+        let message = self.session.text().encode_message(buf)?;
+        if let Some(task) = self.write.take() {
+            task.await?;
+        }
+        self.session.write_async(message)?.await?;
+        Ok(())
+    }
+
+    /// Write a query to the session and parse the response as a value
+    ///
+    /// Other threads cannot use the session between the write and the read.
+    ///
+    /// # Errors
+    /// Will return an error if the query cannot be written or the response cannot be parsed
+    pub async fn query<T>(&mut self, cmd: &str) -> Result<T, Error>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Debug,
+    {
+        let lock = self.session.io_lock().clone();
+        let _io = spawn_blocking(move || Ok(lock.acquire_for_task())).await?;
+
+        self.write_string(cmd).await?;
+        self.read_string()
+            .await?
+            .trim()
+            .parse()
+            .map_err(|e| Error::from_msg(format!("{cmd}: {e:?}")))
+    }
+
+    /// Read the status byte of the device
+    ///
+    /// See `Session::read_status`
+    ///
+    /// # Errors
+    /// Will return an error if the status byte cannot be read
+    pub async fn read_status(&self) -> Result<StatusByte, Error> {
+        blocking(&self.session, |session, _| session.read_status()).await
+    }
+
+    /// Wait for an event enabled for queuing on the session
+    ///
+    /// See `Session::wait_on_event`. The wait stops within `POLL_INTERVAL` of the future being dropped.
+    ///
+    /// # Errors
+    /// Will return an error if no event is received before the timeout
+    pub async fn wait_on_event(
+        &self,
+        event: event::Event,
+        timeout: Duration,
+    ) -> Result<event::EventContext, Error> {
+        blocking(&self.session, move |session, cancelled| {
+            let context = poll_until(timeout, cancelled, |slice| {
+                session.wait_on_event(event, slice)
+            })?;
+
+            if cancelled() {
+                drop(context);
+                return Err(Error::new(
+                    ErrorType::Abort as i32,
+                    Some(session.session_id()),
+                ));
+            }
+            Ok(context)
+        })
+        .await
+    }
+
+    /// Acquire an exclusive lock on the session
+    ///
+    /// See `Session::lock`. If the future is dropped, the attempt stops within `POLL_INTERVAL`, and a lock
+    /// acquired in the meantime is released.
+    ///
+    /// # Errors
+    /// Will return an error if the lock cannot be acquired
+    pub async fn lock(&self, lock_timeout: Duration) -> Result<(), Error> {
+        blocking(&self.session, move |session, cancelled| {
+            poll_until(lock_timeout, cancelled, |slice| session.lock(slice))?;
+
+            if cancelled() {
+                session.unlock()?;
+                return Err(Error::new(
+                    ErrorType::Abort as i32,
+                    Some(session.session_id()),
+                ));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Unlock the session
+    ///
+    /// # Errors
+    /// Will return an error if the session cannot be unlocked
+    pub fn unlock(&self) -> Result<(), Error> {
+        self.session.unlock()
+    }
+
+    /// Get the data received but not yet read, starting a read if there is none
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<&mut Vec<u8>, Error>> {
+        if self.pending.is_empty() {
+            if self.read.is_none() {
+                ready!(self.poll_flush_async(cx))?;
+                self.read = Some(self.session.read_async(self.chunk_size)?);
+            }
+
+            let result = ready!(Pin::new(self.read.as_mut().unwrap()).poll(cx));
+            self.read = None;
+            self.pending = self.complete_read(result?);
+        }
+        Poll::Ready(Ok(&mut self.pending))
+    }
+
+    /// Finish the write in flight, then start writing a copy of `buf`
+    ///
+    /// The whole buffer is accepted once the write is started; its completion is reported by `poll_flush_async`.
+    fn poll_write_async(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Error>> {
+        ready!(self.poll_flush_async(cx))?;
+        self.write = Some(self.session.write_async(buf)?);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    /// Finish the write in flight, if any
+    fn poll_flush_async(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        if let Some(task) = &mut self.write {
+            let result = ready!(Pin::new(task).poll(cx));
+            self.write = None;
+
+            let result = result?;
+            if result.count < result.data.len() {
+                return Poll::Ready(Err(Error::from_msg(format!(
+                    "Incomplete write: {} of {} bytes sent",
+                    result.count,
+                    result.data.len()
+                ))));
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Record the end-of-message state of a completed read, returning its data
+    fn complete_read(&mut self, result: crate::AsyncResult) -> Vec<u8> {
+        self.message_end = result.status != bindings::VI_SUCCESS_MAX_CNT as i32;
+        result.data
+    }
+}
+
+impl From<Session> for AsyncSession {
+    fn from(session: Session) -> Self {
+        Self::new(session)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for AsyncSession {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let data = ready!(self.get_mut().poll_pending(cx))?;
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        data.drain(..len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for AsyncSession {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut().poll_write_async(cx, buf).map_err(Into::into)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().poll_flush_async(cx).map_err(Into::into)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().poll_flush_async(cx).map_err(Into::into)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures::io::AsyncRead for AsyncSession {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let data = ready!(self.get_mut().poll_pending(cx))?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        data.drain(..len);
+        Poll::Ready(Ok(len))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures::io::AsyncWrite for AsyncSession {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut().poll_write_async(cx, buf).map_err(Into::into)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().poll_flush_async(cx).map_err(Into::into)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().poll_flush_async(cx).map_err(Into::into)
+    }
+}
+
+impl Session {
+    /// Wrap the session for use from async code
+    ///
+    /// See `AsyncSession`
+    #[must_use]
+    pub fn into_async(self) -> AsyncSession {
+        AsyncSession::new(self)
+    }
+}
+
+/// Sets its flag when dropped, telling a blocking operation that nobody is waiting for it anymore
+struct CancelOnDrop(Arc<AtomicBool>);
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Run a blocking operation on a clone of the session, off the async executor
+///
+/// The operation is passed a function returning true once the calling future has been dropped
+async fn blocking<T, F>(session: &Session, f: F) -> Result<T, Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&Session, &dyn Fn() -> bool) -> Result<T, Error> + Send + 'static,
+{
+    let session = session.clone();
+    let cancel = CancelOnDrop(Arc::new(AtomicBool::new(false)));
+    let flag = cancel.0.clone();
+
+    let result = spawn_blocking(move || f(&session, &|| flag.load(Ordering::Relaxed))).await;
+    drop(cancel);
+    result
+}
+
+/// Run a closure off the async executor
+///
+/// Within a tokio runtime, the closure runs on its blocking thread pool. Elsewhere, including under other executors
+/// when the `tokio` feature is merely enabled, it runs on a dedicated thread.
+async fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    #[cfg(feature = "tokio")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return match tokio::task::spawn_blocking(f).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(Error::from_msg(format!("Blocking operation failed: {e}"))),
+        };
+    }
+
+    spawn_thread(f).await
+}
+
+/// The outcome of a closure run by `spawn_thread`, and the task waiting for it
+struct ThreadOutcome<T> {
+    result: Option<std::thread::Result<Result<T, Error>>>,
+    waker: Option<Waker>,
+}
+
+/// Run a closure on a dedicated thread, without depending on any executor
+async fn spawn_thread<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    fn lock<T>(outcome: &Mutex<ThreadOutcome<T>>) -> MutexGuard<'_, ThreadOutcome<T>> {
+        outcome.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    let outcome = Arc::new(Mutex::new(ThreadOutcome {
+        result: None,
+        waker: None,
+    }));
+    let thread_outcome = outcome.clone();
+    std::thread::spawn(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+        let waker = {
+            let mut outcome = lock(&thread_outcome);
+            outcome.result = Some(result);
+            outcome.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+
+    let result = std::future::poll_fn(|cx| {
+        let mut outcome = lock(&outcome);
+        if let Some(result) = outcome.result.take() {
+            return Poll::Ready(result);
+        }
+        outcome.waker = Some(cx.waker().clone());
+        Poll::Pending
+    })
+    .await;
+    result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Retry a VISA operation in slices of `POLL_INTERVAL` until it succeeds, fails with anything but a timeout,
+/// the timeout expires, or the operation is cancelled
+fn poll_until<T>(
+    timeout: Duration,
+    cancelled: &dyn Fn() -> bool,
+    mut f: impl FnMut(Duration) -> Result<T, Error>,
+) -> Result<T, Error> {
+    // An overflowing deadline is as good as infinite
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        let remaining = deadline.map_or(AsyncSession::POLL_INTERVAL, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        });
+
+        match f(remaining.min(AsyncSession::POLL_INTERVAL)) {
+            Err(e) if e.status == ErrorType::Tmo && !remaining.is_zero() && !cancelled() => {}
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+
+    #[test]
+    fn test_poll_until() {
+        let mut calls = 0;
+        let result = poll_until(Duration::from_millis(250), &|| false, |slice| {
+            calls += 1;
+            assert!(slice <= AsyncSession::POLL_INTERVAL);
+            Err::<(), _>(Error::new(ErrorType::Tmo as i32, None))
+        });
+        assert_eq!(result.unwrap_err().status, ErrorType::Tmo);
+        assert!(calls > 1);
+
+        let mut calls = 0;
+        let result = poll_until(Duration::from_secs(10), &|| true, |_| {
+            calls += 1;
+            Err::<(), _>(Error::new(ErrorType::Tmo as i32, None))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_cancelled_read() {
+        let mut session = AsyncSession::new(Session::null());
+        session.pending = vec![1];
+        session.message_end = false;
+        session.read = Some(AsyncTask::unstarted(
+            Session::null(),
+            21,
+            vec![2, 3, 4, 5],
+            true,
+        ));
+
+        // Cancel while the read is in flight
+        let mut context = Context::from_waker(Waker::noop());
+        let mut read = Box::pin(session.read_message());
+        assert!(read.as_mut().poll(&mut context).is_pending());
+        drop(read);
+        assert_eq!(session.pending, [1]);
+
+        // The next read resumes the job, and returns all the data
+        crate::async_io::complete_job(bindings::VI_NULL, 21, 0, 3);
+        let message = futures::executor::block_on(session.read_message()).unwrap();
+        assert_eq!(message, [1, 2, 3, 4]);
+        assert!(session.at_message_end());
+    }
+
+    #[test]
+    fn test_spawn_blocking() {
+        // Outside of a tokio runtime, even with the tokio feature
+        let result = futures::executor::block_on(spawn_blocking(|| Ok(1 + 1)));
+        assert_eq!(result.unwrap(), 2);
+
+        let error =
+            futures::executor::block_on(spawn_blocking(|| Err::<(), _>(Error::from_msg("Failed"))));
+        assert!(error.is_err());
+
+        let panic = std::panic::catch_unwind(|| {
+            futures::executor::block_on(spawn_blocking::<()>(|| panic!("Blocking panic")))
+        });
+        assert!(panic.is_err());
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(spawn_blocking(|| Ok(std::thread::current().id())));
+        assert_ne!(result.unwrap(), std::thread::current().id());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_session() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let mut session = get_local_device().into_async();
+        let id: String = session.query("*IDN?").await.unwrap();
+        assert_eq!(id.split(',').count(), 4);
+
+        session.write_all(b"*IDN?\n").await.unwrap();
+        let mut reader = BufReader::new(&mut session);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line.trim_end(), id);
+
+        session.read_status().await.unwrap();
+        session.lock(Duration::from_secs(1)).await.unwrap();
+        session.unlock().unwrap();
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_futures_io() {
+        use futures::io::{AsyncReadExt, AsyncWriteExt};
+
+        futures::executor::block_on(async {
+            let mut session = get_local_device().into_async();
+            session.write_all(b"*IDN?\n").await.unwrap();
+
+            let mut response = [0; 256];
+            let len = session.read(&mut response).await.unwrap();
+            assert!(len > 0);
+            assert!(session.at_message_end());
+
+            session.read_status().await.unwrap();
+        });
+    }
+}
@@ -199,6 +199,7 @@ pub enum AttributeType {
     PxiRecvIntrData = bindings::VI_ATTR_PXI_RECV_INTR_DATA,
     UserData = bindings::VI_ATTR_USER_DATA,
     RetCount = bindings::VI_ATTR_RET_COUNT,
+    Status = bindings::VI_ATTR_STATUS,
     WinBaseAddr = bindings::VI_ATTR_WIN_BASE_ADDR,
     WinSize = bindings::VI_ATTR_WIN_SIZE,
     MemBase = bindings::VI_ATTR_MEM_BASE,
@@ -369,26 +370,28 @@ mod test {
             AttributeType::IntfNum => misc::IntfNum::attribute_type(),
             AttributeType::FileAppendEn => misc::FileAppendEn::attribute_type(),
 
-            AttributeType::JobId => todo!(), //misc::JobId::attribute_type(),
-            AttributeType::EventType => todo!(), //misc::EventType::attribute_type(),
-            AttributeType::SigpStatusId => todo!(), //misc::SigpStatusId::attribute_type(),
-            AttributeType::RecvTrigId => todo!(), //misc::RecvTrigId::attribute_type(),
-            AttributeType::IntrStatusId => todo!(), //misc::IntrStatusId::attribute_type(),
-            AttributeType::RecvIntrLevel => todo!(), //misc::RecvIntrLevel::attribute_type(),
-            AttributeType::OperName => todo!(), //misc::OperName::attribute_type(),
-            AttributeType::RecvTcpipAddr => todo!(), //misc::RecvTcpipAddr::attribute_type(),
+            AttributeType::JobId => misc::JobId::attribute_type(),
+            AttributeType::EventType => misc::EventType::attribute_type(),
+            AttributeType::SigpStatusId => misc::SigpStatusId::attribute_type(),
+            AttributeType::RecvTrigId => misc::RecvTrigId::attribute_type(),
+            AttributeType::IntrStatusId => misc::IntrStatusId::attribute_type(),
+            AttributeType::RecvIntrLevel => misc::RecvIntrLevel::attribute_type(),
+            AttributeType::OperName => misc::OperName::attribute_type(),
+            AttributeType::RecvTcpipAddr => misc::RecvTcpipAddr::attribute_type(),
+            AttributeType::RetCount => misc::RetCount::attribute_type(),
+            AttributeType::Status => misc::Status::attribute_type(),
+
             AttributeType::UserData => todo!(), //misc::UserData::attribute_type(),
-            AttributeType::RetCount => todo!(), //misc::RetCount::attribute_type(),
-            AttributeType::WinBaseAddr => todo!(), //misc::WinBaseAddr::attribute_type(),
-            AttributeType::WinSize => todo!(), //misc::WinSize::attribute_type(),
+            AttributeType::WinBaseAddr => misc::WinBaseAddr::attribute_type(),
+            AttributeType::WinSize => misc::WinSize::attribute_type(),
             AttributeType::Is4882Compliant => todo!(), //misc::Is4882Compliant::attribute_type(),
-            AttributeType::TrigId => todo!(), //misc::TrigId::attribute_type(),
-            AttributeType::WinAccess => todo!(), //misc::WinAccess::attribute_type(),
+            AttributeType::TrigId => todo!(),          //misc::TrigId::attribute_type(),
+            AttributeType::WinAccess => misc::WinAccess::attribute_type(),
             AttributeType::RmSession => todo!(), //misc::RmSession::attribute_type(),
-            AttributeType::ManfId => todo!(), //misc::ManfId::attribute_type(),
-            AttributeType::MemSpace => todo!(), //misc::MemSpace::attribute_type(),
+            AttributeType::ManfId => todo!(),    //misc::ManfId::attribute_type(),
+            AttributeType::MemSpace => misc::MemSpace::attribute_type(),
             AttributeType::ModelCode => todo!(), //misc::ModelCode::attribute_type(),
-            AttributeType::Slot => todo!(),  //misc::Slot::attribute_type(),
+            AttributeType::Slot => todo!(),      //misc::Slot::attribute_type(),
             AttributeType::IntfInstName => todo!(), //misc::IntfInstName::attribute_type(),
             AttributeType::ImmediateServ => todo!(), //misc::ImmediateServ::attribute_type(),
             AttributeType::IntfParentNum => todo!(), //misc::IntfParentNum::attribute_type(),
@@ -2,11 +2,6 @@
 use crate::bindings;
 
 /*
-impl_attr!(
-    ""
-    WinAccess()
-);
-
 impl_attr!(
     ""
     RmSession()
@@ -22,11 +17,6 @@ impl_attr!(
     MemSize32()
 );
 
-impl_attr!(
-    ""
-    MemSpace()
-);
-
 impl_attr!(
     ""
     ModelCode()
@@ -61,71 +51,72 @@ impl_attr!(
 
 impl_attr!(
     ""
-    JobId()
+    RetCount32()
 );
 
 impl_attr!(
     ""
-    EventType()
+    UserData()
 );
 
-impl_attr!(
-    ""
-    SigpStatusId()
-);
+ */
 
 impl_attr!(
-    ""
-    RecvTrigId()
+    "`VI_ATTR_JOB_ID` contains the job ID of the asynchronous operation that has completed."
+    JobId(ReadOnlyU32)
 );
 
 impl_attr!(
-    ""
-    IntrStatusId()
+    "`VI_ATTR_EVENT_TYPE` is the unique logical identifier for the event type of the notification."
+    EventType(u32, crate::event::Event),
+    from = |value| {
+        crate::event::Event::try_from(value).ok().map(Self)
+    }
 );
 
 impl_attr!(
-    ""
-    RetCount32()
+    "`VI_ATTR_STATUS` contains the return code of the operation generating this event."
+    Status(bindings::ViStatus, bindings::ViStatus),
+    from = |value| {
+        Some(Self(value))
+    }
 );
 
 impl_attr!(
-    ""
-    RecvIntrLevel()
+    "`VI_ATTR_RET_COUNT` contains the actual number of elements that were asynchronously transferred."
+    RetCount(ReadOnlyU32)
 );
 
 impl_attr!(
-    ""
-    OperName()
+    "`VI_ATTR_OPER_NAME` contains the name of the operation generating this event."
+    OperName(ReadOnlyString)
 );
 
 impl_attr!(
-    ""
-    RecvTcpipAddr()
+    "`VI_ATTR_SIGP_STATUS_ID` is the 16-bit Status/ID value retrieved during the IACK cycle or from the Signal register."
+    SigpStatusId(ReadOnlyU16)
 );
 
 impl_attr!(
-    ""
-    UserData()
+    "`VI_ATTR_RECV_TRIG_ID` identifies the triggering mechanism on which the specified trigger event was received."
+    RecvTrigId(ReadOnlyI16)
 );
 
 impl_attr!(
-    ""
-    RetCount()
+    "`VI_ATTR_INTR_STATUS_ID` is the 32-bit status/ID retrieved during the IACK cycle."
+    IntrStatusId(ReadOnlyU32)
 );
 
 impl_attr!(
-    ""
-    WinBaseAddr()
+    "`VI_ATTR_RECV_INTR_LEVEL` is the VXI interrupt level on which the interrupt was received."
+    RecvIntrLevel(ReadOnlyI16)
 );
 
 impl_attr!(
-    ""
-    WinSize()
+    "`VI_ATTR_RECV_TCPIP_ADDR` is the address of the remote host from which a `VI_EVENT_TCPIP_CONNECT` connection was received."
+    RecvTcpipAddr(ReadOnlyString)
 );
 
- */
-
 impl_attr!(
     "`VI_ATTR_MAX_QUEUE_LENGTH` specifies the maximum number of events that can be queued at any time on the given session. Events that occur after the queue has become full will be discarded."
     "`VI_ATTR_MAX_QUEUE_LENGTH` is a Read/Write attribute until the first time `viEnableEvent()` is called on a session. Thereafter, this attribute is Read Only."
@@ -283,11 +274,11 @@ impl_attr!(
 #[repr(u32)]
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub enum WrBufOperModeType {
-    #[default]
-    /// the buffer is flushed when an END indicator is written to the buffer, or when the buffer fills up.
+    /// the write buffer is flushed when an END indicator is written to the buffer, or when the buffer fills up, and also every time a `viPrintf()` (or related) operation completes.
     OnAccess = bindings::VI_FLUSH_ON_ACCESS,
 
-    /// the write buffer is flushed under the same conditions, and also every time a `viPrintf()` (or related) operation completes.
+    #[default]
+    /// the buffer is flushed when an END indicator is written to the buffer, or when the buffer fills up.
     OnFull = bindings::VI_FLUSH_WHEN_FULL,
 }
 
@@ -487,6 +478,47 @@ impl_attr!(
     }
 );
 
+/// How the mapped window of a session can be accessed
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowAccess {
+    /// No window is mapped
+    NotMapped = bindings::VI_NMAPPED,
+
+    /// The window must be accessed with `viPeekXX()` and `viPokeXX()`
+    UseOperations = bindings::VI_USE_OPERS,
+
+    /// The window can be accessed by dereferencing its address
+    Dereference = bindings::VI_DEREF_ADDR,
+}
+impl_attr!(
+    "`VI_ATTR_WIN_ACCESS` specifies the modes in which the current window may be accessed."
+    "If `VI_NMAPPED`, the window is not currently mapped. If `VI_USE_OPERS`, the window must be accessed with the `viPeekXX()` and `viPokeXX()` operations."
+    "If `VI_DEREF_ADDR`, the window can also be accessed by dereferencing the address returned by `viMapAddress()`."
+    WinAccess(u16, WindowAccess),
+
+    from = |value| {
+        match u32::from(value) {
+            bindings::VI_NMAPPED => Some(Self(WindowAccess::NotMapped)),
+            bindings::VI_USE_OPERS => Some(Self(WindowAccess::UseOperations)),
+            bindings::VI_DEREF_ADDR => Some(Self(WindowAccess::Dereference)),
+            _ => None,
+        }
+    }
+);
+
+impl_attr!(
+    "`VI_ATTR_WIN_BASE_ADDR` specifies the base address of the interface bus to which this window is mapped."
+    "If the value of `VI_ATTR_WIN_ACCESS` is `VI_NMAPPED`, the value of this attribute is undefined."
+    WinBaseAddr(ReadOnlyU32)
+);
+
+impl_attr!(
+    "`VI_ATTR_WIN_SIZE` specifies the size of the region mapped to this window."
+    "If the value of `VI_ATTR_WIN_ACCESS` is `VI_NMAPPED`, the value of this attribute is undefined."
+    WinSize(ReadOnlyU32)
+);
+
 impl_attr!(
     "`VI_ATTR_SRC_INCREMENT` is used in the `viMoveInXX()` operations to specify by how many elements the source offset is to be incremented after every transfer."
     "The default value of this attribute is 1 (that is, the source address will be incremented by 1 after each transfer), and the `viMoveInXX()` operations move from consecutive elements."
@@ -587,3 +619,12 @@ impl_attr!(
         Some(Self(value))
     }
 );
+
+impl_attr!(
+    "`VI_ATTR_MEM_SPACE` specifies the `VXIbus` address space used by the device. The four types are A16, A24, A32 or A64 memory address space."
+    "On servant sessions, it is the address space of the memory allocated with `viMemAlloc()`."
+    MemSpace(u16, crate::AddressSpace),
+    from = |value| {
+        crate::AddressSpace::try_from(value).ok().map(Self)
+    }
+);
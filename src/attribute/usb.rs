@@ -32,6 +32,8 @@ impl_attr!(
 /// `VI_ATTR_USB_RECV_INTR_DATA` contains the actual received data from the USB Interrupt.
 ///
 /// The passed in data buffer must be of size at least equal to the value of `VI_ATTR_USB_RECV_INTR_SIZE`.
+///
+/// To read interrupt data of any size, use `EventContext::usb_interrupt_data` or `Session::usb_interrupts`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct UsbRecvIntrData<const USB_RECV_INTR_SIZE: usize>(pub [u8; USB_RECV_INTR_SIZE]);
 impl<const USB_RECV_INTR_SIZE: usize> UsbRecvIntrData<{ USB_RECV_INTR_SIZE }> {
@@ -0,0 +1,208 @@
+//! Buffered reading from a session
+//!
+//! `BufferedSession` implements `std::io::BufRead`, so that instrument responses can be consumed with
+//! `lines()`, `read_until` or `fill_buf`:
+//! ```ignore
+//! session.write_string("MEAS:VOLT? (@1:8)")?;
+//!
+//! let mut reader = session.buffered()?;
+//! let message = reader.read_message()?;
+//! ```
+//!
+//! Reads stop at the end of a message (END or the termination character), so that a single
+//! `fill_buf` never blocks waiting for data the device has not been asked for.
+//! Reading past the end of the last message waits for more data, and fails with
+//! `std::io::ErrorKind::TimedOut` once the session timeout expires.
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{attribute::misc::RdBufSize, bindings, error::Error, Session};
+use std::io::{BufRead, Read};
+
+/// A reader over a session, buffering the data returned by the device
+///
+/// The buffer holds at most one message; `at_message_end` reports whether the buffered data completes it.
+#[derive(Debug)]
+pub struct BufferedSession<'a> {
+    session: &'a mut Session,
+    buf: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+    message_end: bool,
+}
+
+impl<'a> BufferedSession<'a> {
+    /// Size of the buffer used when the session reports no formatted I/O read buffer
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    /// Create a buffered reader, sized from the `RdBufSize` attribute of the session
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub fn new(session: &'a mut Session) -> Result<Self, Error> {
+        let capacity = match session.get_attribute::<RdBufSize>()? {
+            0 => Self::DEFAULT_CAPACITY,
+            size => size as usize,
+        };
+        Ok(Self::with_capacity(session, capacity))
+    }
+
+    /// Create a buffered reader with a buffer of the given size
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero
+    #[must_use]
+    pub fn with_capacity(session: &'a mut Session, capacity: usize) -> Self {
+        assert!(capacity > 0, "Buffer capacity must not be zero");
+        Self {
+            session,
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            message_end: true,
+        }
+    }
+
+    /// Get the underlying session
+    #[must_use]
+    pub fn session(&self) -> &Session {
+        self.session
+    }
+
+    /// Get the size of the buffer
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Get the data currently buffered, without reading from the device
+    #[must_use]
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// Returns true if the last read completed a message (END or termination character received)
+    ///
+    /// When false, the device has more data to send for the current message
+    #[must_use]
+    pub fn at_message_end(&self) -> bool {
+        self.message_end
+    }
+
+    /// Read the remainder of the current message, up to END or the termination character
+    ///
+    /// Buffered data is returned first.
+    ///
+    /// # Errors
+    /// Will return an error if the read fails or times out
+    pub fn read_message(&mut self) -> Result<Vec<u8>, Error> {
+        let mut message = vec![];
+        loop {
+            let data = self.fill()?;
+            message.extend_from_slice(data);
+
+            let len = data.len();
+            self.consume(len);
+            if self.message_end || len == 0 {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Read the remainder of the current message as a string, using the session text options
+    ///
+    /// # Errors
+    /// Will return an error if the read fails, or the data cannot be decoded
+    pub fn read_message_string(&mut self) -> Result<String, Error> {
+        let message = self.read_message()?;
+        self.session.text().decode_message(&message)
+    }
+
+    /// Discard the buffered data, returning the underlying session
+    #[must_use]
+    pub fn into_inner(self) -> &'a mut Session {
+        self.session
+    }
+
+    /// Get the buffered data, reading from the device if the buffer is empty
+    fn fill(&mut self) -> Result<&[u8], Error> {
+        if self.pos >= self.filled {
+            self.pos = 0;
+            self.filled = 0;
+            self.filled = self.read_device()?;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    /// Read from the device into the buffer, recording whether the message ended
+    fn read_device(&mut self) -> Result<usize, Error> {
+        let vi = self.session.session_id();
+        let buf = &mut self.buf;
+
+        let mut bytes_read = 0;
+        let status = Error::wrap_binding_status(Some(vi), || unsafe {
+            bindings::viRead(
+                vi,
+                buf.as_mut_ptr(),
+                u32::try_from(buf.len()).unwrap_or(u32::MAX),
+                &raw mut bytes_read,
+            )
+        })?;
+
+        self.message_end = status != bindings::VI_SUCCESS_MAX_CNT as i32;
+        Ok(bytes_read as usize)
+    }
+}
+
+impl Read for BufferedSession<'_> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        let data = self.fill_buf()?;
+        let len = data.len().min(out.len());
+        out[..len].copy_from_slice(&data[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for BufferedSession<'_> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.fill().map_err(std::io::Error::from)
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.filled);
+    }
+}
+
+impl Session {
+    /// Wrap the session in a buffered reader, sized from the `RdBufSize` attribute
+    ///
+    /// See `BufferedSession`
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub fn buffered(&mut self) -> Result<BufferedSession<'_>, Error> {
+        BufferedSession::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+
+    #[test]
+    fn test_buffered_lines() {
+        let mut session = get_local_device();
+        session.write_string("*IDN?").unwrap();
+
+        let mut reader = BufferedSession::with_capacity(&mut session, 4);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert!(reader.at_message_end());
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.trim_end().split(',').count(), 4);
+    }
+}
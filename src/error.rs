@@ -101,14 +101,28 @@ impl Error {
     /// Wrap a call to a VISA binding
     ///
     /// # Errors
-    /// Retiurns an error if the status code is not `VI_SUCCESS`
+    /// Returns an error if the status code is not `VI_SUCCESS`
     pub fn wrap_binding<F>(session: Option<bindings::ViSession>, f: F) -> Result<(), Self>
+    where
+        F: FnOnce() -> i32,
+    {
+        Self::wrap_binding_status(session, f).map(|_| ())
+    }
+
+    /// Wrap a call to a VISA binding, keeping the success code
+    ///
+    /// Some operations report how they completed through success codes,
+    /// such as `VI_SUCCESS_TERM_CHAR` or `VI_SUCCESS_NESTED_SHARED`
+    ///
+    /// # Errors
+    /// Returns an error if the status code is not a success code
+    pub fn wrap_binding_status<F>(session: Option<bindings::ViSession>, f: F) -> Result<i32, Self>
     where
         F: FnOnce() -> i32,
     {
         let status = f();
         if Self::ERROR_OK.contains(&status) {
-            Ok(())
+            Ok(status)
         } else {
             Err(Self::new(status, session))
         }
@@ -124,6 +138,17 @@ impl Display for Error {
     }
 }
 
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        let kind = match e.status {
+            ErrorType::Tmo => std::io::ErrorKind::TimedOut,
+            ErrorType::ConnLost => std::io::ErrorKind::ConnectionAborted,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
         Self {
@@ -4,7 +4,11 @@
     reason = "Needed for compatibility with the VISA library"
 )]
 
-use crate::{bindings, error::Error};
+use crate::{
+    attribute::{self, misc, usb},
+    bindings,
+    error::Error,
+};
 
 /// The types of events that can be handled
 #[repr(u32)]
@@ -63,6 +67,17 @@ pub enum HandlingMechanism {
     SuspendHandler = bindings::VI_SUSPEND_HNDLR,
 }
 
+/// Convert the outcome of a handler into the status returned to VISA
+///
+/// Panics must not unwind into VISA, so they are caught and reported as `VI_ERROR_SYSTEM_ERROR`
+fn handler_status(result: std::thread::Result<Result<(), Error>>) -> bindings::ViStatus {
+    match result {
+        Ok(Ok(())) => bindings::VI_SUCCESS as bindings::ViStatus,
+        Ok(Err(e)) => e.status as bindings::ViStatus,
+        Err(_) => bindings::VI_ERROR_SYSTEM_ERROR,
+    }
+}
+
 /// A simple handler for VISA events.
 ///
 /// If you need to make use of the visa `user_data` field, use [`HandlerWithData`] instead.
@@ -71,22 +86,33 @@ pub trait Handler {
     ///
     /// # Errors
     /// Should return an error if the event cannot be handled
-    fn handle(
-        session: bindings::ViSession,
-        event_type: Event,
-        event: bindings::ViEvent,
-    ) -> Result<(), Error>;
+    fn handle(session: bindings::ViSession, event: &EventContext) -> Result<(), Error>;
 }
 impl<T: Handler> HandlerWithData for T {
     type Data = std::ffi::c_void;
 
     fn handle(
         session: bindings::ViSession,
-        event_type: Event,
-        event: bindings::ViEvent,
-        _user_data: &Self::Data,
+        event: &EventContext,
+        _user_data: Option<&Self::Data>,
     ) -> Result<(), Error> {
-        Self::handle(session, event_type, event)
+        Self::handle(session, event)
+    }
+
+    unsafe extern "system" fn c_handler(
+        session: bindings::ViSession,
+        event_type: bindings::ViEventType,
+        event: bindings::ViEvent,
+        _user_data: bindings::ViAddr,
+    ) -> bindings::ViStatus {
+        // No user data; the pointer may be null and must not be dereferenced
+        let Ok(event_type) = Event::try_from(event_type) else {
+            return bindings::VI_ERROR_INV_EVENT;
+        };
+
+        let event = EventContext::borrowed(session, event, event_type);
+        let result = std::panic::catch_unwind(|| <Self as Handler>::handle(session, &event));
+        handler_status(result)
     }
 }
 
@@ -99,13 +125,14 @@ pub trait HandlerWithData {
 
     /// Handles the event itself
     ///
+    /// `user_data` is `None` if the handler was installed without data.
+    ///
     /// # Errors
     /// Should return an error if the event cannot be handled
     fn handle(
         session: bindings::ViSession,
-        event_type: Event,
-        event: bindings::ViEvent,
-        user_data: &Self::Data,
+        event: &EventContext,
+        user_data: Option<&Self::Data>,
     ) -> Result<(), Error>;
 
     /// Convert the handler to a C handler
@@ -122,14 +149,14 @@ pub trait HandlerWithData {
             return bindings::VI_ERROR_INV_EVENT;
         };
 
-        // Janky cast to get the user data
-        let user_data: *mut Self::Data = user_data.cast::<Self::Data>();
-        let user_data: &Self::Data = &*user_data;
+        // The user handle is null if the handler was installed without data
+        let user_data: Option<&Self::Data> = user_data.cast::<Self::Data>().as_ref();
 
-        match Self::handle(session, event_type, event, user_data) {
-            Ok(()) => bindings::VI_SUCCESS as bindings::ViStatus,
-            Err(e) => e.status as bindings::ViStatus,
-        }
+        let event = EventContext::borrowed(session, event, event_type);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            Self::handle(session, &event, user_data)
+        }));
+        handler_status(result)
     }
 
     /// Convert the handler to a C handler
@@ -142,3 +169,210 @@ pub trait HandlerWithData {
         Some(Self::c_handler)
     }
 }
+
+/// The context of an event occurrence, giving access to its attributes
+///
+/// Contexts returned by `Session::wait_on_event` are closed when dropped.
+/// Contexts passed to handlers belong to VISA, and are only valid for the duration of the call.
+#[derive(Debug)]
+pub struct EventContext {
+    session: bindings::ViSession,
+    event: bindings::ViEvent,
+    event_type: Event,
+    owned: bool,
+}
+impl EventContext {
+    /// Take ownership of a context returned by `viWaitOnEvent`
+    pub(crate) fn owned(
+        session: bindings::ViSession,
+        event: bindings::ViEvent,
+        event_type: Event,
+    ) -> Self {
+        Self {
+            session,
+            event,
+            event_type,
+            owned: true,
+        }
+    }
+
+    /// Wrap a context passed to a handler, which VISA closes itself
+    pub(crate) fn borrowed(
+        session: bindings::ViSession,
+        event: bindings::ViEvent,
+        event_type: Event,
+    ) -> Self {
+        Self {
+            session,
+            event,
+            event_type,
+            owned: false,
+        }
+    }
+
+    /// The type of the event that occurred
+    #[must_use]
+    pub fn event_type(&self) -> Event {
+        self.event_type
+    }
+
+    /// Get the raw VISA event context
+    #[must_use]
+    pub fn as_raw(&self) -> bindings::ViEvent {
+        self.event
+    }
+
+    /// Read an attribute of the event
+    /// See `attribute::misc` for the event attributes
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub fn get_attribute<T>(&self) -> Result<T::Value, Error>
+    where
+        T: attribute::AsViReadable,
+    {
+        let raw = unsafe { self.get_attribute_raw::<T::RawValue>(T::VI_ATTR) }?;
+        let attr = T::from_vi(raw).ok_or_else(|| Error::from_msg("Invalid attribute value"))?;
+        Ok(attr.into_value())
+    }
+
+    /// Read an attribute of the event
+    ///
+    /// # Safety
+    /// This function is unsafe because it does not check the validity of the attribute
+    /// being read. Use `get_attribute` instead.
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub unsafe fn get_attribute_raw<T>(&self, attr: bindings::ViAttr) -> Result<T, Error> {
+        let mut value: T = std::mem::zeroed::<T>();
+        Error::wrap_binding(Some(self.session), || unsafe {
+            let value = (&raw mut value).cast::<std::ffi::c_void>();
+            bindings::viGetAttribute(self.event, attr, value)
+        })?;
+        Ok(value)
+    }
+
+    /// The return code of the operation that generated the event (`VI_ATTR_STATUS`)
+    ///
+    /// # Errors
+    /// Will return an error if the event has no status
+    pub fn status(&self) -> Result<bindings::ViStatus, Error> {
+        self.get_attribute::<misc::Status>()
+    }
+
+    /// The outcome of the operation that generated the event
+    ///
+    /// Returns the success code of the operation, or the error it failed with.
+    ///
+    /// # Errors
+    /// Will return an error if the operation failed, or the event has no status
+    pub fn result(&self) -> Result<bindings::ViStatus, Error> {
+        let status = self.status()?;
+        if status < 0 {
+            return Err(Error::new(status, Some(self.session)));
+        }
+        Ok(status)
+    }
+
+    /// The job id of the completed asynchronous operation (`VI_ATTR_JOB_ID`)
+    ///
+    /// # Errors
+    /// Will return an error if the event is not an I/O completion
+    pub fn job_id(&self) -> Result<bindings::ViJobId, Error> {
+        self.get_attribute::<misc::JobId>()
+    }
+
+    /// The number of elements transferred by the completed asynchronous operation (`VI_ATTR_RET_COUNT`)
+    ///
+    /// # Errors
+    /// Will return an error if the event is not an I/O completion
+    pub fn ret_count(&self) -> Result<usize, Error> {
+        Ok(self.get_attribute::<misc::RetCount>()? as usize)
+    }
+
+    /// The name of the operation that generated the event (`VI_ATTR_OPER_NAME`)
+    ///
+    /// # Errors
+    /// Will return an error if the event has no operation name
+    pub fn oper_name(&self) -> Result<String, Error> {
+        self.get_attribute::<misc::OperName>()
+    }
+
+    /// The Status/ID value of a VXI signal or interrupt (`VI_ATTR_SIGP_STATUS_ID`)
+    ///
+    /// # Errors
+    /// Will return an error if the event is not a VXI signal
+    pub fn sigp_status_id(&self) -> Result<u16, Error> {
+        self.get_attribute::<misc::SigpStatusId>()
+    }
+
+    /// The trigger line on which a trigger event was received (`VI_ATTR_RECV_TRIG_ID`)
+    ///
+    /// # Errors
+    /// Will return an error if the event is not a trigger
+    pub fn recv_trig_id(&self) -> Result<i16, Error> {
+        self.get_attribute::<misc::RecvTrigId>()
+    }
+
+    /// The 32-bit status/ID of a VXI/VME interrupt (`VI_ATTR_INTR_STATUS_ID`)
+    ///
+    /// # Errors
+    /// Will return an error if the event is not an interrupt
+    pub fn intr_status_id(&self) -> Result<u32, Error> {
+        self.get_attribute::<misc::IntrStatusId>()
+    }
+
+    /// The VXI interrupt level on which an interrupt was received (`VI_ATTR_RECV_INTR_LEVEL`)
+    ///
+    /// # Errors
+    /// Will return an error if the event is not an interrupt
+    pub fn recv_intr_level(&self) -> Result<i16, Error> {
+        self.get_attribute::<misc::RecvIntrLevel>()
+    }
+
+    /// The address of the remote host of a TCP/IP connection event (`VI_ATTR_RECV_TCPIP_ADDR`)
+    ///
+    /// # Errors
+    /// Will return an error if the event is not a TCP/IP connection
+    pub fn recv_tcpip_addr(&self) -> Result<String, Error> {
+        self.get_attribute::<misc::RecvTcpipAddr>()
+    }
+
+    /// The data of a USB interrupt (`VI_ATTR_USB_RECV_INTR_DATA`), sized by `VI_ATTR_USB_RECV_INTR_SIZE`
+    ///
+    /// # Errors
+    /// Will return an error if the event is not a USB interrupt
+    pub fn usb_interrupt_data(&self) -> Result<Vec<u8>, Error> {
+        let size = self.get_attribute::<usb::UsbRecvIntrSize>()?;
+        let mut data = vec![0u8; usize::from(size)];
+        Error::wrap_binding(Some(self.session), || unsafe {
+            bindings::viGetAttribute(
+                self.event,
+                attribute::AttributeType::UsbRecvIntrData as u32,
+                data.as_mut_ptr().cast(),
+            )
+        })?;
+        Ok(data)
+    }
+}
+impl Drop for EventContext {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { bindings::viClose(self.event) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_event_context() {
+        let context = EventContext::borrowed(0, 0, Event::IoCompletion);
+        assert_eq!(context.event_type(), Event::IoCompletion);
+        assert_eq!(context.as_raw(), 0);
+        assert!(context.job_id().is_err());
+    }
+}
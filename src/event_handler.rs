@@ -0,0 +1,453 @@
+//! Closure-based event handlers
+//!
+//! `Session::on_event` installs a closure as the handler of an event, and returns a `HandlerGuard`
+//! that uninstalls it when dropped:
+//! ```ignore
+//! let _guard = session.on_event(Event::ServiceReq, |event| {
+//!     println!("Service requested: {:?}", event.event_type());
+//! })?;
+//! ```
+//!
+//! All closures are dispatched through a single C trampoline, which looks them up in a registry by the user handle
+//! VISA passes back. Panics are caught before they reach VISA, and reported by `HandlerGuard::take_panic`.
+//!
+//! `Session::add_event_handler` installs a `HandlerWithData` implementation instead, and returns an
+//! `InstalledHandler` owning the data passed to it.
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{
+    bindings,
+    error::Error,
+    event::{Event, EventContext, HandlerWithData, HandlingMechanism},
+    Session,
+};
+use std::{
+    collections::HashMap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+};
+
+type Callback = Box<dyn FnMut(&EventContext) + Send>;
+
+/// A registered closure, and the message of the last panic it raised
+struct Entry {
+    callback: Mutex<Callback>,
+    panic: Mutex<Option<String>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    handlers: HashMap<usize, Arc<Entry>>,
+
+    /// Number of guards per session and event, and whether the handler mechanism was enabled by them
+    enabled: HashMap<(bindings::ViSession, u32), (usize, bool)>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    lock(REGISTRY.get_or_init(Mutex::default))
+}
+
+/// Turn a panic payload into a message
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// The C handler shared by all closures, which finds the closure from the user handle
+unsafe extern "system" fn trampoline(
+    session: bindings::ViSession,
+    event_type: bindings::ViEventType,
+    event: bindings::ViEvent,
+    user_handle: bindings::ViAddr,
+) -> bindings::ViStatus {
+    let Ok(event_type) = Event::try_from(event_type) else {
+        return bindings::VI_ERROR_INV_EVENT;
+    };
+
+    // The guard may have been dropped while VISA was dispatching the event
+    let Some(entry) = registry().handlers.get(&user_handle.addr()).cloned() else {
+        return bindings::VI_SUCCESS as bindings::ViStatus;
+    };
+
+    let context = EventContext::borrowed(session, event, event_type);
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut callback = lock(&entry.callback);
+        callback(&context);
+    }));
+
+    match result {
+        Ok(()) => bindings::VI_SUCCESS as bindings::ViStatus,
+        Err(payload) => {
+            *lock(&entry.panic) = Some(panic_message(payload.as_ref()));
+            bindings::VI_ERROR_SYSTEM_ERROR
+        }
+    }
+}
+
+/// A closure installed as an event handler by `Session::on_event`
+///
+/// Dropping the guard uninstalls the handler. The event is disabled for the handler mechanism once the last
+/// guard for it is dropped, if it was enabled by `on_event`.
+#[derive(Debug)]
+#[must_use = "Dropping the guard uninstalls the handler"]
+pub struct HandlerGuard {
+    session: Session,
+    event: Event,
+    id: usize,
+    removed: bool,
+}
+impl HandlerGuard {
+    /// The event the handler is installed for
+    #[must_use]
+    pub fn event(&self) -> Event {
+        self.event
+    }
+
+    /// Take the panic raised by the closure since the last call, if any
+    #[must_use]
+    pub fn take_panic(&self) -> Option<Error> {
+        let entry = registry().handlers.get(&self.id).cloned()?;
+        let message = lock(&entry.panic).take()?;
+        Some(Error::from_msg(format!(
+            "Event handler panicked: {message}"
+        )))
+    }
+
+    /// Uninstall the handler, reporting any error
+    ///
+    /// # Errors
+    /// Will return an error if the handler cannot be uninstalled, or the event cannot be disabled
+    pub fn remove(mut self) -> Result<(), Error> {
+        self.uninstall()
+    }
+
+    fn uninstall(&mut self) -> Result<(), Error> {
+        if self.removed {
+            return Ok(());
+        }
+        self.removed = true;
+
+        let vi = self.session.session_id();
+        let event = self.event as u32;
+        let uninstalled = Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viUninstallHandler(
+                vi,
+                event,
+                Some(trampoline),
+                std::ptr::without_provenance_mut(self.id),
+            )
+        });
+
+        let disable = {
+            let mut registry = registry();
+            registry.handlers.remove(&self.id);
+
+            let key = (vi, event);
+            let (guards, enabled) = registry.enabled.get(&key).copied().unwrap_or((1, false));
+            if guards > 1 {
+                registry.enabled.insert(key, (guards - 1, enabled));
+                false
+            } else {
+                registry.enabled.remove(&key);
+                enabled
+            }
+        };
+
+        if disable {
+            self.session
+                .disable_event(self.event, HandlingMechanism::Handler)?;
+        }
+        uninstalled
+    }
+}
+impl Drop for HandlerGuard {
+    fn drop(&mut self) {
+        self.uninstall().ok();
+    }
+}
+
+/// A `HandlerWithData` installed by `Session::add_event_handler`, with the data passed to it
+///
+/// Dropping it uninstalls the handler, then frees the data. If the handler cannot be uninstalled, the data is
+/// leaked rather than freed while VISA may still use it.
+#[must_use = "Dropping the handler uninstalls it"]
+pub struct InstalledHandler<H: HandlerWithData> {
+    session: Session,
+    event: Event,
+    data: Option<Box<H::Data>>,
+    removed: bool,
+}
+impl<H: HandlerWithData> InstalledHandler<H> {
+    /// A handler that is not installed yet, and is not uninstalled when dropped
+    pub(crate) fn uninstalled(session: Session, event: Event, data: Option<Box<H::Data>>) -> Self {
+        Self {
+            session,
+            event,
+            data,
+            removed: true,
+        }
+    }
+
+    /// Install the handler, passing it the address of the data
+    pub(crate) fn install(&mut self) -> Result<(), Error> {
+        let vi = self.session.session_id();
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viInstallHandler(vi, self.event as u32, H::into(), self.user_handle())
+        })?;
+        self.removed = false;
+        Ok(())
+    }
+
+    /// The event the handler is installed for
+    #[must_use]
+    pub fn event(&self) -> Event {
+        self.event
+    }
+
+    /// The data passed to the handler
+    #[must_use]
+    pub fn data(&self) -> Option<&H::Data> {
+        self.data.as_deref()
+    }
+
+    /// Uninstall the handler, reporting any error
+    ///
+    /// # Errors
+    /// Will return an error if the handler cannot be uninstalled
+    pub fn remove(mut self) -> Result<(), Error> {
+        self.uninstall()
+    }
+
+    fn user_handle(&self) -> bindings::ViAddr {
+        self.data.as_deref().map_or(std::ptr::null_mut(), |data| {
+            std::ptr::from_ref(data).cast_mut().cast()
+        })
+    }
+
+    fn uninstall(&mut self) -> Result<(), Error> {
+        if self.removed {
+            return Ok(());
+        }
+        self.removed = true;
+
+        let vi = self.session.session_id();
+        let uninstalled = Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viUninstallHandler(vi, self.event as u32, H::into(), self.user_handle())
+        });
+        if uninstalled.is_err() {
+            if let Some(data) = self.data.take() {
+                Box::leak(data);
+            }
+        }
+        uninstalled
+    }
+}
+impl<H: HandlerWithData> std::fmt::Debug for InstalledHandler<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InstalledHandler")
+            .field("session", &self.session)
+            .field("event", &self.event)
+            .field("removed", &self.removed)
+            .finish_non_exhaustive()
+    }
+}
+impl<H: HandlerWithData> Drop for InstalledHandler<H> {
+    fn drop(&mut self) {
+        self.uninstall().ok();
+    }
+}
+
+impl Session {
+    /// Install a closure as the handler of an event
+    ///
+    /// The event is enabled for the handler mechanism, if it is not already.
+    /// The closure runs on a VISA thread, and must not block for long.
+    ///
+    /// # Errors
+    /// Will return an error if the handler cannot be installed, or the event cannot be enabled
+    pub fn on_event<F>(&self, event: Event, callback: F) -> Result<HandlerGuard, Error>
+    where
+        F: FnMut(&EventContext) + Send + 'static,
+    {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+        let vi = self.session_id();
+        let key = (vi, event as u32);
+        {
+            let mut registry = registry();
+            registry.handlers.insert(
+                id,
+                Arc::new(Entry {
+                    callback: Mutex::new(Box::new(callback)),
+                    panic: Mutex::new(None),
+                }),
+            );
+            registry.enabled.entry(key).or_insert((0, false)).0 += 1;
+        }
+
+        // From here on, the guard cleans up after any failure
+        let guard = HandlerGuard {
+            session: self.clone(),
+            event,
+            id,
+            removed: false,
+        };
+
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viInstallHandler(
+                vi,
+                event as u32,
+                Some(trampoline),
+                std::ptr::without_provenance_mut(id),
+            )
+        })?;
+
+        let status = Error::wrap_binding_status(Some(vi), || unsafe {
+            bindings::viEnableEvent(
+                vi,
+                event as u32,
+                HandlingMechanism::Handler as u16,
+                bindings::VI_NULL,
+            )
+        })?;
+        if status != bindings::VI_SUCCESS_EVENT_EN as i32 {
+            if let Some(entry) = registry().enabled.get_mut(&key) {
+                entry.1 = true;
+            }
+        }
+
+        Ok(guard)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+
+    fn register(callback: impl FnMut(&EventContext) + Send + 'static) -> usize {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(usize::MAX / 2);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        registry().handlers.insert(
+            id,
+            Arc::new(Entry {
+                callback: Mutex::new(Box::new(callback)),
+                panic: Mutex::new(None),
+            }),
+        );
+        id
+    }
+
+    fn dispatch(id: usize) -> bindings::ViStatus {
+        unsafe {
+            trampoline(
+                0,
+                bindings::VI_EVENT_TRIG,
+                0,
+                std::ptr::without_provenance_mut(id),
+            )
+        }
+    }
+
+    #[test]
+    fn test_trampoline() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let id = register(move |event| sender.send(event.event_type()).unwrap());
+        assert_eq!(dispatch(id), bindings::VI_SUCCESS as bindings::ViStatus);
+        assert_eq!(receiver.try_recv().unwrap(), Event::Trig);
+
+        // Unknown handles are ignored
+        assert_eq!(
+            dispatch(id + 1000),
+            bindings::VI_SUCCESS as bindings::ViStatus
+        );
+    }
+
+    #[test]
+    fn test_trampoline_panic() {
+        let id = register(|_| panic!("Handler failure"));
+        assert_eq!(dispatch(id), bindings::VI_ERROR_SYSTEM_ERROR);
+
+        let entry = registry().handlers[&id].clone();
+        assert_eq!(lock(&entry.panic).as_deref(), Some("Handler failure"));
+    }
+
+    #[test]
+    fn test_installed_handler() {
+        struct Counter;
+        impl HandlerWithData for Counter {
+            type Data = AtomicUsize;
+
+            fn handle(
+                _: bindings::ViSession,
+                _: &EventContext,
+                count: Option<&Self::Data>,
+            ) -> Result<(), Error> {
+                count.unwrap().fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        let handler = InstalledHandler::<Counter>::uninstalled(
+            Session::null(),
+            Event::Trig,
+            Some(Box::new(AtomicUsize::new(0))),
+        );
+        let status =
+            unsafe { Counter::c_handler(0, bindings::VI_EVENT_TRIG, 0, handler.user_handle()) };
+        assert_eq!(status, bindings::VI_SUCCESS as bindings::ViStatus);
+        assert_eq!(handler.data().unwrap().load(Ordering::Relaxed), 1);
+        handler.remove().unwrap();
+
+        // Data that VISA may still use is leaked when the handler cannot be uninstalled
+        let mut handler = InstalledHandler::<Counter>::uninstalled(
+            Session::null(),
+            Event::Trig,
+            Some(Box::new(AtomicUsize::new(0))),
+        );
+        handler.removed = false;
+        assert!(handler.uninstall().is_err());
+        assert!(handler.data().is_none());
+    }
+
+    #[test]
+    fn test_on_event() {
+        let mut session = get_local_device();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let guard = session
+            .on_event(Event::ServiceReq, move |event| {
+                sender.send(event.event_type()).ok();
+            })
+            .unwrap();
+
+        session.write_string("*SRE 16").unwrap();
+        session.write_string("*IDN?").unwrap();
+        let event = receiver
+            .recv_timeout(std::time::Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(event, Event::ServiceReq);
+        assert!(guard.take_panic().is_none());
+
+        session.read_string().unwrap();
+        guard.remove().unwrap();
+    }
+}
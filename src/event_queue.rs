@@ -0,0 +1,217 @@
+//! Queued VISA events
+//!
+//! `Session::event_queue` enables the queue mechanism for an event, and returns an `EventQueue` to wait on its
+//! occurrences. The queue is a blocking iterator over `EventContext`s:
+//! ```ignore
+//! let mut queue = session.event_queue(Event::ServiceReq)?;
+//! for event in &mut queue {
+//!     let event = event?;
+//!     let status = session.read_status()?;
+//! }
+//! ```
+//!
+//! VISA discards events arriving while the queue is full, and reports it with `VI_WARN_QUEUE_OVERFLOW` along with
+//! the next event dequeued. `EventQueue::overflows` counts these reports.
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{
+    attribute::misc::MaxQueueLength,
+    bindings,
+    error::{Error, ErrorType},
+    event::{Event, EventContext, HandlingMechanism},
+    Session,
+};
+use std::time::Duration;
+
+/// A queue of the occurrences of an event on a session
+///
+/// Iterating over the queue blocks until the next event arrives, and never ends on its own.
+/// Dropping the queue disables the event and discards the pending occurrences, if the queue enabled it.
+#[derive(Debug)]
+#[must_use = "Dropping the queue disables the event"]
+pub struct EventQueue {
+    session: Session,
+    event: Event,
+    overflows: usize,
+    enabled: bool,
+}
+impl EventQueue {
+    /// Enable the event for the queue mechanism on the session, if it was not already
+    pub(crate) fn enable(session: Session, event: Event) -> Result<Self, Error> {
+        let status = Error::wrap_binding_status(Some(session.session_id()), || unsafe {
+            bindings::viEnableEvent(
+                session.session_id(),
+                event as u32,
+                HandlingMechanism::Queue as u16,
+                bindings::VI_NULL,
+            )
+        })?;
+
+        Ok(Self {
+            session,
+            event,
+            overflows: 0,
+            enabled: status != bindings::VI_SUCCESS_EVENT_EN as i32,
+        })
+    }
+
+    /// The event type the queue was created for
+    #[must_use]
+    pub fn event(&self) -> Event {
+        self.event
+    }
+
+    /// The number of times VISA reported that events were discarded because the queue was full
+    ///
+    /// Each report covers at least one lost event; VISA does not say how many.
+    #[must_use]
+    pub fn overflows(&self) -> usize {
+        self.overflows
+    }
+
+    /// The maximum number of events the queue can hold (`VI_ATTR_MAX_QUEUE_LENGTH`)
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub fn max_length(&self) -> Result<u32, Error> {
+        self.session.get_attribute::<MaxQueueLength>()
+    }
+
+    /// Wait up to `timeout` for the next event
+    ///
+    /// Returns `None` if no event arrived in time. Use `Duration::ZERO` to dequeue without waiting.
+    ///
+    /// # Errors
+    /// Will return an error if the event cannot be waited on
+    pub fn try_next(&mut self, timeout: Duration) -> Result<Option<EventContext>, Error> {
+        dequeued(
+            self.session.wait_on_event_status(self.event, timeout),
+            &mut self.overflows,
+        )
+    }
+
+    /// Discard the occurrences of the event waiting in the queue
+    ///
+    /// # Errors
+    /// Will return an error if the events cannot be discarded
+    pub fn discard(&self) -> Result<(), Error> {
+        self.session
+            .discard_events(self.event, HandlingMechanism::Queue)
+    }
+}
+
+impl Iterator for EventQueue {
+    type Item = Result<EventContext, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let forever = Duration::from_millis(u64::from(bindings::VI_TMO_INFINITE));
+        self.try_next(forever).transpose()
+    }
+}
+
+impl Drop for EventQueue {
+    fn drop(&mut self) {
+        if self.enabled {
+            self.session
+                .disable_event(self.event, HandlingMechanism::Queue)
+                .ok();
+            self.discard().ok();
+        }
+    }
+}
+
+/// Interpret the result of a wait on the queue, counting overflows and treating timeouts as empty
+fn dequeued(
+    result: Result<(EventContext, bindings::ViStatus), Error>,
+    overflows: &mut usize,
+) -> Result<Option<EventContext>, Error> {
+    match result {
+        Ok((context, status)) => {
+            if status == bindings::VI_WARN_QUEUE_OVERFLOW as i32 {
+                *overflows += 1;
+            }
+            Ok(Some(context))
+        }
+        Err(e) if e.status == ErrorType::Tmo => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl Session {
+    /// Receive the occurrences of an event through the VISA event queue
+    ///
+    /// The event is enabled for the queue mechanism, if it was not already.
+    ///
+    /// # Errors
+    /// Will return an error if the event cannot be enabled
+    pub fn event_queue(&self, event: Event) -> Result<EventQueue, Error> {
+        EventQueue::enable(self.clone(), event)
+    }
+
+    /// Receive the occurrences of an event through a VISA event queue holding up to `length` events
+    ///
+    /// The length of the queue can only be set before the first event is enabled on the session.
+    ///
+    /// # Errors
+    /// Will return an error if the length cannot be set, or the event cannot be enabled
+    pub fn event_queue_with_length(&self, event: Event, length: u32) -> Result<EventQueue, Error> {
+        let mut session = self.clone();
+        session.set_attribute::<MaxQueueLength>(length)?;
+        EventQueue::enable(session, event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+
+    #[test]
+    fn test_dequeued() {
+        let mut overflows = 0;
+        let event = |status| Ok((EventContext::borrowed(0, 0, Event::Trig), status));
+
+        let context = dequeued(event(bindings::VI_SUCCESS as i32), &mut overflows).unwrap();
+        assert_eq!(context.unwrap().event_type(), Event::Trig);
+        assert_eq!(overflows, 0);
+
+        let context = dequeued(
+            event(bindings::VI_WARN_QUEUE_OVERFLOW as i32),
+            &mut overflows,
+        );
+        assert!(context.unwrap().is_some());
+        assert_eq!(overflows, 1);
+
+        let timeout = dequeued(
+            Err(Error::new(bindings::VI_ERROR_TMO, None)),
+            &mut overflows,
+        );
+        assert!(timeout.unwrap().is_none());
+
+        let failure = dequeued(
+            Err(Error::new(bindings::VI_ERROR_INV_EVENT, None)),
+            &mut overflows,
+        );
+        assert!(failure.is_err());
+    }
+
+    #[test]
+    fn test_event_queue() {
+        let mut session = get_local_device();
+        let mut queue = session
+            .event_queue_with_length(Event::ServiceReq, 10)
+            .unwrap();
+        assert_eq!(queue.max_length().unwrap(), 10);
+        assert!(queue.try_next(Duration::ZERO).unwrap().is_none());
+
+        session.write_string("*SRE 16").unwrap();
+        session.write_string("*IDN?").unwrap();
+        let event = queue.next().unwrap().unwrap();
+        assert_eq!(event.event_type(), Event::ServiceReq);
+        assert_eq!(queue.overflows(), 0);
+        session.read_string().unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+//! Streams of VISA events
+//!
+//! Enabled with the `futures` feature. `Session::event_stream` installs a handler for an event type, and returns a
+//! `futures::Stream` yielding each occurrence:
+//! ```ignore
+//! let mut requests = session.service_requests()?;
+//! while let Some(_) = requests.next().await {
+//!     let status = session.read_status()?;
+//! }
+//! ```
+//!
+//! The stream is driven by the VISA callback, and does not depend on any particular executor.
+//! Dropping the stream uninstalls the handler, and disables the event if the stream enabled it.
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{
+    bindings,
+    error::Error,
+    event::{self, Event, HandlingMechanism},
+    InstalledHandler, Session,
+};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+/// Events received by the handler and not yet yielded by the stream
+#[derive(Debug, Default)]
+struct StreamState {
+    events: VecDeque<Event>,
+    waker: Option<Waker>,
+}
+
+type SharedState = Mutex<StreamState>;
+
+fn lock(state: &SharedState) -> MutexGuard<'_, StreamState> {
+    state
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Handler queueing each event occurrence on the stream passed as user data
+struct StreamHandler;
+impl event::HandlerWithData for StreamHandler {
+    type Data = SharedState;
+
+    fn handle(
+        _: bindings::ViSession,
+        event: &event::EventContext,
+        state: Option<&Self::Data>,
+    ) -> Result<(), Error> {
+        let state = state
+            .ok_or_else(|| Error::from_msg("Event stream handler installed without a stream"))?;
+        let waker = {
+            let mut state = lock(state);
+            state.events.push_back(event.event_type());
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+}
+
+/// A stream of the occurrences of an event on a session
+///
+/// Yields the type of each occurrence. The stream never ends on its own.
+#[derive(Debug)]
+#[must_use = "Dropping the stream uninstalls its handler"]
+pub struct EventStream {
+    session: Session,
+    handler: InstalledHandler<StreamHandler>,
+    enabled: bool,
+}
+impl EventStream {
+    /// The event type the stream was created for
+    #[must_use]
+    pub fn event(&self) -> Event {
+        self.handler.event()
+    }
+
+    /// Take the next event if one was already received, without waiting
+    pub fn try_next(&mut self) -> Option<Event> {
+        lock(self.state()).events.pop_front()
+    }
+
+    fn state(&self) -> &SharedState {
+        self.handler
+            .data()
+            .expect("The stream handler is installed with its state")
+    }
+}
+
+impl futures::Stream for EventStream {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = lock(self.state());
+        let Some(event) = state.events.pop_front() else {
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        };
+        Poll::Ready(Some(event))
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        // The handler is uninstalled afterwards, when dropped
+        if self.enabled {
+            self.session
+                .disable_event(self.event(), HandlingMechanism::Handler)
+                .ok();
+        }
+    }
+}
+
+impl Session {
+    /// Receive the occurrences of an event as a `futures::Stream`
+    ///
+    /// The event is enabled for the handler mechanism, if it was not already.
+    ///
+    /// # Errors
+    /// Will return an error if the handler cannot be installed, or the event cannot be enabled
+    pub fn event_stream(&self, event: Event) -> Result<EventStream, Error> {
+        let mut stream = EventStream {
+            session: self.clone(),
+            handler: self.add_event_handler(event, Some(Box::default()))?,
+            enabled: false,
+        };
+
+        let status = Error::wrap_binding_status(Some(self.session_id()), || unsafe {
+            bindings::viEnableEvent(
+                self.session_id(),
+                event as u32,
+                HandlingMechanism::Handler as u16,
+                bindings::VI_NULL,
+            )
+        })?;
+        stream.enabled = status != bindings::VI_SUCCESS_EVENT_EN as i32;
+        Ok(stream)
+    }
+
+    /// Receive the service requests of the device as a `futures::Stream`
+    ///
+    /// See `Session::event_stream`
+    ///
+    /// # Errors
+    /// Will return an error if the handler cannot be installed, or the event cannot be enabled
+    pub fn service_requests(&self) -> Result<EventStream, Error> {
+        self.event_stream(Event::ServiceReq)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+    use event::HandlerWithData;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_stream_handler() {
+        let state = SharedState::default();
+        for event in [Event::ServiceReq, Event::Trig] {
+            let context = event::EventContext::borrowed(0, 0, event);
+            StreamHandler::handle(0, &context, Some(&state)).unwrap();
+        }
+
+        let mut stream = EventStream {
+            session: Session::null(),
+            handler: InstalledHandler::uninstalled(
+                Session::null(),
+                Event::All,
+                Some(Box::new(state)),
+            ),
+            enabled: false,
+        };
+        assert_eq!(stream.try_next(), Some(Event::ServiceReq));
+        let next = futures::executor::block_on(stream.next());
+        assert_eq!(next, Some(Event::Trig));
+        assert_eq!(stream.try_next(), None);
+
+        // A handler installed without data fails instead of dereferencing a null handle
+        let context = event::EventContext::borrowed(0, 0, Event::Trig);
+        assert!(StreamHandler::handle(0, &context, None).is_err());
+    }
+
+    #[test]
+    fn test_service_requests() {
+        let mut session = get_local_device();
+        let mut requests = session.service_requests().unwrap();
+        assert_eq!(requests.event(), Event::ServiceReq);
+
+        session.write_string("*SRE 16").unwrap();
+        session.write_string("*IDN?").unwrap();
+        let next = futures::executor::block_on(requests.next());
+        assert_eq!(next, Some(Event::ServiceReq));
+        session.read_string().unwrap();
+    }
+}
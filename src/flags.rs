@@ -0,0 +1,140 @@
+//! Helper macro for register values made of individual bits
+//!
+//! Do not use directly
+
+/// Macro simplifying the definition of a bitflag type over a raw register value
+///
+/// Generates the named flag constants, the usual set operations, and a `Debug`
+/// implementation listing the names of the flags that are set.
+macro_rules! impl_flags {
+    (
+        $($docs:literal)*
+        $name:ident($raw:ty) {
+            $(
+                $($flag_docs:literal)*
+                $flag:ident = $bit:expr;
+            )*
+        }
+    ) => {
+        $(#[doc = $docs])*
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+        pub struct $name($raw);
+        impl $name {
+            $(
+                $(#[doc = $flag_docs])*
+                pub const $flag: Self = Self($bit);
+            )*
+
+            /// All flags, by name
+            const NAMED: &[(&'static str, Self)] = &[$((stringify!($flag), Self::$flag)),*];
+
+            /// A value with no flags set
+            #[must_use]
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            /// Build a value from the raw register bits
+            #[must_use]
+            pub const fn from_bits(bits: $raw) -> Self {
+                Self(bits)
+            }
+
+            /// Get the raw register bits
+            #[must_use]
+            pub const fn bits(self) -> $raw {
+                self.0
+            }
+
+            /// Returns true if no flags are set
+            #[must_use]
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// Returns true if all flags in `other` are set
+            #[must_use]
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Returns true if any flag in `other` is set
+            #[must_use]
+            pub const fn intersects(self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            /// Set the flags in `other`
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Clear the flags in `other`
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+        }
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let mut remaining = self.0;
+                let mut first = true;
+                write!(f, "{}(", stringify!($name))?;
+                for (name, flag) in Self::NAMED {
+                    if flag.0 != 0 && self.contains(*flag) {
+                        if !first {
+                            write!(f, " | ")?;
+                        }
+                        write!(f, "{name}")?;
+                        remaining &= !flag.0;
+                        first = false;
+                    }
+                }
+                if remaining != 0 {
+                    if !first {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{remaining:#x}")?;
+                }
+                write!(f, ")")
+            }
+        }
+        impl From<$raw> for $name {
+            fn from(bits: $raw) -> Self {
+                Self(bits)
+            }
+        }
+        impl From<$name> for $raw {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+        impl std::ops::BitOr for $name {
+            type Output = Self;
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+        impl std::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+        impl std::ops::BitAnd for $name {
+            type Output = Self;
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+        impl std::ops::BitAndAssign for $name {
+            fn bitand_assign(&mut self, rhs: Self) {
+                self.0 &= rhs.0;
+            }
+        }
+        impl std::ops::Not for $name {
+            type Output = Self;
+            fn not(self) -> Self {
+                Self(!self.0)
+            }
+        }
+    };
+}
@@ -0,0 +1,1753 @@
+//! Type-safe formatted I/O
+//!
+//! The `printf!`, `scanf!` and `queryf!` macros accept the format strings of `viPrintf`, `viScanf` and `viQueryf`,
+//! but the formatting and parsing is done on the Rust side: arguments are checked against the conversions before
+//! any I/O takes place, and no value is ever passed through C varargs.
+//!
+//! The data goes through the VISA formatted I/O buffers (`viBufWrite` and `viBufRead`), so these macros can be
+//! mixed with the buffer helpers of `Session`.
+//!
+//! # Format strings
+//! A conversion has the form `%[flags][width][,count][.precision][!ob|!ol][modifier]conversion`
+//! - flags: `-` left justify, `+` or space for the sign of positive numbers, `0` zero padding, `#` alternate form
+//! - `,count` makes the conversion operate on a comma separated list of `count` numbers.
+//!   `,#` uses the length of the argument when writing, and reads as many numbers as are present
+//! - modifiers select the element size of binary data: `h` 16-bit, `l` 32-bit, `ll` or `L` 64-bit integers,
+//!   `z` 32-bit and `Z` 64-bit floats; the default is 8-bit integers
+//! - `!ob` and `!ol` select big (default) or little endian binary data
+//!
+//! | Conversion | Argument | Written as | Read as |
+//! |------------|----------|------------|---------|
+//! | `d` `i` `u` `o` `x` `X` | integer | integer | integer |
+//! | `f` `e` `E` `g` `G` | float | number | number |
+//! | `s` | `str`, `String` or `[u8]` | string | whitespace delimited word |
+//! | `c` | `char`, string | character | single byte |
+//! | `t` `T` | `String` or `Vec<u8>` | - | rest of the message |
+//! | `b` | slice or `Vec` of numbers | IEEE 488.2 definite length block | IEEE 488.2 block |
+//! | `y` | slice or `Vec` of numbers | raw binary | raw binary |
+//!
+//! VISA counts given by an extra argument (`%#s`, `%#b`, `%,#d`) are taken from the argument itself:
+//! the length of a slice for writes, and a growing buffer for reads. For `b` and `y`, a numeric width is the number of elements.
+//!
+//! Every read consumes a complete response message; data left after the last conversion is discarded.
+//!
+//! Raw data can be sent through the same buffers with a `FormattedIo` handle, see `Session::formatted_io`.
+//!
+//! # Example
+//! ```ignore
+//! printf!(&session, "SOUR:VOLT %.3f\n", 1.5)?;
+//!
+//! let mut samples: Vec<i16> = vec![];
+//! queryf!(&session, "CURV?\n"; "%hb", samples)?;
+//! ```
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "Needed for compatibility with the VISA library"
+)]
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{
+    attribute::misc::{WrBufOperMode, WrBufOperModeType, WrBufSize},
+    bindings,
+    error::{Error, ErrorType},
+    Session,
+};
+
+/// The kind of value held by an argument, checked against the conversions of a format string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A single integer
+    Integer,
+
+    /// A single float
+    Float,
+
+    /// UTF-8 text
+    Text,
+
+    /// Raw bytes, usable both as text and as an array of 8-bit integers
+    Bytes,
+
+    /// An array of integers, with the size of an element in bytes
+    IntegerArray(usize),
+
+    /// An array of floats, with the size of an element in bytes
+    FloatArray(usize),
+}
+
+/// A value written by `printf!`
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrintfValue<'a> {
+    /// A single integer
+    Int(i128),
+
+    /// A single float
+    Float(f64),
+
+    /// UTF-8 text
+    Text(&'a str),
+
+    /// Raw bytes
+    Bytes(&'a [u8]),
+
+    /// An array of integers, with the size of an element in bytes
+    Integers(Vec<i128>, usize),
+
+    /// An array of floats, with the size of an element in bytes
+    Floats(Vec<f64>, usize),
+}
+impl PrintfValue<'_> {
+    /// The kind of the value
+    #[must_use]
+    pub fn kind(&self) -> ArgKind {
+        match self {
+            Self::Int(_) => ArgKind::Integer,
+            Self::Float(_) => ArgKind::Float,
+            Self::Text(_) => ArgKind::Text,
+            Self::Bytes(_) => ArgKind::Bytes,
+            Self::Integers(_, size) => ArgKind::IntegerArray(*size),
+            Self::Floats(_, size) => ArgKind::FloatArray(*size),
+        }
+    }
+}
+
+/// A value read by `scanf!`, before it is stored in the target
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanfValue {
+    /// A single integer
+    Int(i128),
+
+    /// A single float
+    Float(f64),
+
+    /// Text or bytes
+    Text(Vec<u8>),
+
+    /// A comma separated list of integers
+    Integers(Vec<i128>),
+
+    /// A comma separated list of floats
+    Floats(Vec<f64>),
+
+    /// Binary data, made of elements of the size of the target elements
+    Binary {
+        /// The raw data
+        data: Vec<u8>,
+
+        /// Byte order of the elements
+        little_endian: bool,
+    },
+}
+
+/// An argument of `printf!`
+pub trait PrintfArg {
+    /// Get the value to format
+    fn printf_value(&self) -> PrintfValue<'_>;
+}
+
+/// A target of `scanf!`
+pub trait ScanfTarget {
+    /// The kind of value the target can hold
+    fn kind(&self) -> ArgKind;
+
+    /// Store a value read from the device
+    ///
+    /// # Errors
+    /// Will return an error if the value does not fit in the target
+    fn assign(&mut self, value: ScanfValue) -> Result<(), Error>;
+}
+
+impl<T: PrintfArg + ?Sized> PrintfArg for &T {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        (**self).printf_value()
+    }
+}
+
+impl PrintfArg for str {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Text(self)
+    }
+}
+
+impl PrintfArg for String {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Text(self)
+    }
+}
+
+impl PrintfArg for char {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Int(i128::from(u32::from(*self)))
+    }
+}
+
+impl PrintfArg for [u8] {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Bytes(self)
+    }
+}
+
+impl PrintfArg for Vec<u8> {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Bytes(self)
+    }
+}
+
+impl<const N: usize> PrintfArg for [u8; N] {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Bytes(self)
+    }
+}
+
+impl ScanfTarget for String {
+    fn kind(&self) -> ArgKind {
+        ArgKind::Text
+    }
+
+    fn assign(&mut self, value: ScanfValue) -> Result<(), Error> {
+        match value {
+            ScanfValue::Text(bytes) => {
+                *self = String::from_utf8(bytes).map_err(|e| e.utf8_error())?;
+                Ok(())
+            }
+            _ => Err(mismatched_target()),
+        }
+    }
+}
+
+impl ScanfTarget for Vec<u8> {
+    fn kind(&self) -> ArgKind {
+        ArgKind::Bytes
+    }
+
+    fn assign(&mut self, value: ScanfValue) -> Result<(), Error> {
+        match value {
+            ScanfValue::Text(bytes) | ScanfValue::Binary { data: bytes, .. } => *self = bytes,
+            ScanfValue::Integers(values) => {
+                *self = values
+                    .into_iter()
+                    .map(|v| u8::try_from(v).map_err(|_| out_of_range(v)))
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(mismatched_target()),
+        }
+        Ok(())
+    }
+}
+
+/// Implement the argument traits for integer types
+macro_rules! impl_integer_args {
+    ($to:expr => $($t:ty),*) => { $(
+        impl PrintfArg for $t {
+            fn printf_value(&self) -> PrintfValue<'_> {
+                PrintfValue::Int($to(*self))
+            }
+        }
+
+        impl ScanfTarget for $t {
+            fn kind(&self) -> ArgKind {
+                ArgKind::Integer
+            }
+
+            fn assign(&mut self, value: ScanfValue) -> Result<(), Error> {
+                match value {
+                    ScanfValue::Int(v) => {
+                        *self = <$t>::try_from(v).map_err(|_| out_of_range(v))?;
+                        Ok(())
+                    }
+                    _ => Err(mismatched_target()),
+                }
+            }
+        }
+    )* };
+}
+impl_integer_args!(i128::from => u8, i8, u16, i16, u32, i32, u64, i64);
+impl_integer_args!(|v| v as i128 => usize, isize);
+
+/// Implement the argument traits for slices, arrays and vectors of numbers, other than bytes
+///
+/// `to` converts an element to the value written, `from` converts a value read to an element
+macro_rules! impl_array_args {
+    ($kind:ident, $value:ident, to = $to:expr, from = $from:expr => $($t:ty),*) => { $(
+        impl PrintfArg for [$t] {
+            fn printf_value(&self) -> PrintfValue<'_> {
+                PrintfValue::$value(
+                    self.iter().map(|&v| $to(v)).collect(),
+                    std::mem::size_of::<$t>(),
+                )
+            }
+        }
+
+        impl PrintfArg for Vec<$t> {
+            fn printf_value(&self) -> PrintfValue<'_> {
+                self.as_slice().printf_value()
+            }
+        }
+
+        impl<const N: usize> PrintfArg for [$t; N] {
+            fn printf_value(&self) -> PrintfValue<'_> {
+                self.as_slice().printf_value()
+            }
+        }
+
+        impl ScanfTarget for Vec<$t> {
+            fn kind(&self) -> ArgKind {
+                ArgKind::$kind(std::mem::size_of::<$t>())
+            }
+
+            fn assign(&mut self, value: ScanfValue) -> Result<(), Error> {
+                match value {
+                    ScanfValue::$value(values) => {
+                        *self = values.into_iter().map($from).collect::<Result<_, Error>>()?;
+                    }
+                    ScanfValue::Binary { data, little_endian } => {
+                        *self = data
+                            .chunks_exact(std::mem::size_of::<$t>())
+                            .map(|chunk| {
+                                let bytes = chunk.try_into().map_err(|_| mismatched_target())?;
+                                Ok(if little_endian {
+                                    <$t>::from_le_bytes(bytes)
+                                } else {
+                                    <$t>::from_be_bytes(bytes)
+                                })
+                            })
+                            .collect::<Result<_, Error>>()?;
+                    }
+                    _ => return Err(mismatched_target()),
+                }
+                Ok(())
+            }
+        }
+    )* };
+}
+impl_array_args!(
+    IntegerArray, Integers,
+    to = i128::from,
+    from = |v| TryFrom::try_from(v).map_err(|_| out_of_range(v))
+    => i8, u16, i16, u32, i32, u64, i64
+);
+impl_array_args!(FloatArray, Floats, to = f64::from, from = |v| Ok(v as f32) => f32);
+impl_array_args!(FloatArray, Floats, to = f64::from, from = Ok => f64);
+
+impl PrintfArg for f32 {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Float(f64::from(*self))
+    }
+}
+
+impl PrintfArg for f64 {
+    fn printf_value(&self) -> PrintfValue<'_> {
+        PrintfValue::Float(*self)
+    }
+}
+
+impl ScanfTarget for f32 {
+    fn kind(&self) -> ArgKind {
+        ArgKind::Float
+    }
+
+    fn assign(&mut self, value: ScanfValue) -> Result<(), Error> {
+        match value {
+            ScanfValue::Float(v) => {
+                *self = v as f32;
+                Ok(())
+            }
+            _ => Err(mismatched_target()),
+        }
+    }
+}
+
+impl ScanfTarget for f64 {
+    fn kind(&self) -> ArgKind {
+        ArgKind::Float
+    }
+
+    fn assign(&mut self, value: ScanfValue) -> Result<(), Error> {
+        match value {
+            ScanfValue::Float(v) => {
+                *self = v;
+                Ok(())
+            }
+            _ => Err(mismatched_target()),
+        }
+    }
+}
+
+//------------ Format strings ------------------------------------------
+
+/// An element count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Count {
+    Fixed(usize),
+    FromArg,
+}
+
+/// Size modifier of a conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Modifier {
+    #[default]
+    None,
+    Short,
+    Long,
+    LongLong,
+    Single,
+    Double,
+}
+impl Modifier {
+    /// Size of a binary element, in bytes
+    fn element_size(self) -> usize {
+        match self {
+            Self::None => 1,
+            Self::Short => 2,
+            Self::Long | Self::Single => 4,
+            Self::LongLong | Self::Double => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Self::Single | Self::Double)
+    }
+}
+
+/// A single conversion of a format string
+#[derive(Debug, Clone, Copy, Default)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "Mirrors the flags of a C conversion specification"
+)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alt: bool,
+    width: Option<usize>,
+    array: Option<Count>,
+    precision: Option<usize>,
+    little_endian: bool,
+    modifier: Modifier,
+    conversion: u8,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Segment<'f> {
+    Literal(&'f [u8]),
+    Conversion(Spec),
+}
+
+fn parse_format(format: &str) -> Result<Vec<Segment<'_>>, Error> {
+    let bytes = format.as_bytes();
+    let mut segments = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        while i < bytes.len() && bytes[i] != b'%' {
+            i += 1;
+        }
+        if i > start {
+            segments.push(Segment::Literal(&bytes[start..i]));
+        }
+        if i == bytes.len() {
+            break;
+        }
+
+        i += 1;
+        if bytes.get(i) == Some(&b'%') {
+            segments.push(Segment::Literal(&bytes[i..=i]));
+            i += 1;
+        } else {
+            let spec;
+            (spec, i) = Spec::parse(bytes, i)?;
+            segments.push(Segment::Conversion(spec));
+        }
+    }
+
+    Ok(segments)
+}
+
+fn conversions<'a>(segments: &'a [Segment<'_>]) -> impl Iterator<Item = &'a Spec> {
+    segments.iter().filter_map(|segment| match segment {
+        Segment::Conversion(spec) => Some(spec),
+        Segment::Literal(_) => None,
+    })
+}
+
+fn parse_number(bytes: &[u8], i: &mut usize) -> Option<usize> {
+    let start = *i;
+    while bytes.get(*i).is_some_and(u8::is_ascii_digit) {
+        *i += 1;
+    }
+    std::str::from_utf8(&bytes[start..*i]).ok()?.parse().ok()
+}
+
+fn is_integer_conversion(conversion: u8) -> bool {
+    b"diuoxX".contains(&conversion)
+}
+
+fn is_float_conversion(conversion: u8) -> bool {
+    b"feEgG".contains(&conversion)
+}
+
+impl Spec {
+    /// Parse a conversion, starting after the `%`
+    fn parse(bytes: &[u8], mut i: usize) -> Result<(Self, usize), Error> {
+        let mut spec = Self::default();
+        while let Some(&c) = bytes.get(i) {
+            match c {
+                b'-' => spec.left = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'0' => spec.zero = true,
+                b'#' => spec.alt = true,
+                _ => break,
+            }
+            i += 1;
+        }
+
+        spec.width = parse_number(bytes, &mut i);
+        if bytes.get(i) == Some(&b',') {
+            i += 1;
+            spec.array = Some(if bytes.get(i) == Some(&b'#') {
+                i += 1;
+                Count::FromArg
+            } else {
+                Count::Fixed(
+                    parse_number(bytes, &mut i)
+                        .ok_or_else(|| invalid_format("Missing array size after ','"))?,
+                )
+            });
+        }
+        if bytes.get(i) == Some(&b'.') {
+            i += 1;
+            if bytes.get(i) == Some(&b'#') {
+                return Err(unsupported_format("Precision from an argument ('.#')"));
+            }
+            spec.precision = Some(parse_number(bytes, &mut i).unwrap_or(0));
+        }
+
+        if bytes[i..].starts_with(b"!ob") {
+            i += 3;
+        } else if bytes[i..].starts_with(b"!ol") {
+            spec.little_endian = true;
+            i += 3;
+        }
+
+        spec.modifier = match bytes.get(i..).unwrap_or_default() {
+            [b'l', b'l', ..] | [b'L', ..] => Modifier::LongLong,
+            [b'h', ..] => Modifier::Short,
+            [b'l', ..] => Modifier::Long,
+            [b'z', ..] => Modifier::Single,
+            [b'Z', ..] => Modifier::Double,
+            _ => Modifier::None,
+        };
+        i += match spec.modifier {
+            Modifier::None => 0,
+            Modifier::LongLong if bytes[i] == b'l' => 2,
+            _ => 1,
+        };
+
+        spec.conversion = *bytes
+            .get(i)
+            .ok_or_else(|| invalid_format("Incomplete conversion at the end of the format"))?;
+        match spec.conversion {
+            b'd' | b'i' | b'u' | b'o' | b'x' | b'X' | b'f' | b'e' | b'E' | b'g' | b'G' | b's'
+            | b'c' | b't' | b'T' | b'b' | b'y' => {}
+            b'[' | b'B' | b'n' | b'a' | b'A' | b'p' => {
+                return Err(unsupported_format(format!(
+                    "Conversion '%{}'",
+                    char::from(spec.conversion)
+                )))
+            }
+            c => {
+                return Err(invalid_format(format!(
+                    "Unknown conversion '%{}'",
+                    char::from(c)
+                )))
+            }
+        }
+
+        if spec.array.is_some()
+            && !is_integer_conversion(spec.conversion)
+            && !is_float_conversion(spec.conversion)
+        {
+            return Err(invalid_format("Arrays (',') require a numeric conversion"));
+        }
+
+        Ok((spec, i + 1))
+    }
+
+    /// Check that an argument can be used with the conversion
+    fn check(&self, kind: ArgKind, write: bool) -> Result<(), Error> {
+        let conversion = self.conversion;
+        let accepted = if self.array.is_some() {
+            if is_integer_conversion(conversion) {
+                matches!(kind, ArgKind::IntegerArray(_) | ArgKind::Bytes)
+            } else {
+                matches!(kind, ArgKind::FloatArray(_))
+            }
+        } else {
+            match conversion {
+                b'b' | b'y' => {
+                    let size = self.modifier.element_size();
+                    match kind {
+                        ArgKind::FloatArray(n) => self.modifier.is_float() && (write || n == size),
+                        ArgKind::IntegerArray(n) => {
+                            !self.modifier.is_float() && (write || n == size)
+                        }
+                        ArgKind::Bytes => self.modifier == Modifier::None,
+                        _ => false,
+                    }
+                }
+                b's' => matches!(kind, ArgKind::Text | ArgKind::Bytes),
+                b't' | b'T' => !write && matches!(kind, ArgKind::Text | ArgKind::Bytes),
+                b'c' => {
+                    matches!(kind, ArgKind::Text | ArgKind::Bytes)
+                        || (write && kind == ArgKind::Integer)
+                }
+                c if is_integer_conversion(c) => kind == ArgKind::Integer,
+                _ => kind == ArgKind::Float,
+            }
+        };
+
+        if accepted {
+            Ok(())
+        } else {
+            Err(invalid_format(format!(
+                "Conversion '%{}' cannot be used with {kind:?} arguments",
+                char::from(conversion)
+            )))
+        }
+    }
+
+    //------------ Writing ---------------------------------------------
+
+    fn write(&self, out: &mut Vec<u8>, value: &PrintfValue<'_>) -> Result<(), Error> {
+        match (self.conversion, value) {
+            (b'b' | b'y', _) => self.write_binary(out, value),
+
+            (_, PrintfValue::Int(v)) if self.conversion == b'c' => {
+                let c = u32::try_from(*v)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| out_of_range(*v))?;
+                self.pad(out, "", c.to_string().as_bytes(), false);
+                Ok(())
+            }
+            (_, PrintfValue::Int(v)) => {
+                self.write_integer(out, *v);
+                Ok(())
+            }
+            (_, PrintfValue::Float(v)) => {
+                self.write_float(out, *v);
+                Ok(())
+            }
+
+            (b'c', PrintfValue::Text(s)) => {
+                let c = s
+                    .chars()
+                    .next()
+                    .ok_or_else(|| invalid_format("Empty character"))?;
+                self.pad(out, "", c.to_string().as_bytes(), false);
+                Ok(())
+            }
+            (b'c', PrintfValue::Bytes(b)) => {
+                let c = b.first().ok_or_else(|| invalid_format("Empty character"))?;
+                self.pad(out, "", &[*c], false);
+                Ok(())
+            }
+            (_, PrintfValue::Text(s)) => {
+                let s = match self.precision {
+                    Some(precision) => s.char_indices().nth(precision).map_or(*s, |(i, _)| &s[..i]),
+                    None => s,
+                };
+                self.pad(out, "", s.as_bytes(), false);
+                Ok(())
+            }
+            (b's', PrintfValue::Bytes(b)) => {
+                let b = match self.precision {
+                    Some(precision) => &b[..precision.min(b.len())],
+                    None => b,
+                };
+                self.pad(out, "", b, false);
+                Ok(())
+            }
+
+            (_, PrintfValue::Bytes(b)) => {
+                let values: Vec<i128> = b.iter().map(|&v| i128::from(v)).collect();
+                self.write_list(out, &values, |out, v| self.write_integer(out, *v))
+            }
+            (_, PrintfValue::Integers(values, _)) => {
+                self.write_list(out, values, |out, v| self.write_integer(out, *v))
+            }
+            (_, PrintfValue::Floats(values, _)) => {
+                self.write_list(out, values, |out, v| self.write_float(out, *v))
+            }
+        }
+    }
+
+    /// Select the elements written by an array or binary conversion
+    fn take<T>(count: Option<Count>, values: &[T]) -> Result<&[T], Error> {
+        match count {
+            Some(Count::Fixed(n)) => values.get(..n).ok_or_else(|| {
+                invalid_format(format!("Expected {n} elements, got {}", values.len()))
+            }),
+            _ => Ok(values),
+        }
+    }
+
+    fn write_list<T>(
+        &self,
+        out: &mut Vec<u8>,
+        values: &[T],
+        write: impl Fn(&mut Vec<u8>, &T),
+    ) -> Result<(), Error> {
+        for (i, value) in Self::take(self.array, values)?.iter().enumerate() {
+            if i > 0 {
+                out.push(b',');
+            }
+            write(out, value);
+        }
+        Ok(())
+    }
+
+    fn write_binary(&self, out: &mut Vec<u8>, value: &PrintfValue<'_>) -> Result<(), Error> {
+        let count = if self.alt {
+            None
+        } else {
+            self.width.map(Count::Fixed)
+        };
+
+        let size = self.modifier.element_size();
+        let mut data = vec![];
+        match value {
+            PrintfValue::Bytes(values) => data.extend_from_slice(Self::take(count, values)?),
+            PrintfValue::Integers(values, _) => {
+                for &v in Self::take(count, values)? {
+                    let min = -(1i128 << (size * 8 - 1));
+                    let max = (1i128 << (size * 8)) - 1;
+                    if v < min || v > max {
+                        return Err(out_of_range(v));
+                    }
+
+                    if self.little_endian {
+                        data.extend_from_slice(&v.to_le_bytes()[..size]);
+                    } else {
+                        data.extend_from_slice(&v.to_be_bytes()[16 - size..]);
+                    }
+                }
+            }
+            PrintfValue::Floats(values, _) => {
+                for &v in Self::take(count, values)? {
+                    match (self.modifier, self.little_endian) {
+                        (Modifier::Single, false) => data.extend((v as f32).to_be_bytes()),
+                        (Modifier::Single, true) => data.extend((v as f32).to_le_bytes()),
+                        (_, false) => data.extend(v.to_be_bytes()),
+                        (_, true) => data.extend(v.to_le_bytes()),
+                    }
+                }
+            }
+            _ => return Err(mismatched_target()),
+        }
+
+        if self.conversion == b'b' {
+            let length = data.len().to_string();
+            if length.len() > 9 {
+                return Err(invalid_format(
+                    "Block too large for a definite length header",
+                ));
+            }
+            out.extend(format!("#{}{length}", length.len()).bytes());
+        }
+        out.extend(data);
+        Ok(())
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Write a field, padded to the width
+    fn pad(&self, out: &mut Vec<u8>, prefix: &str, body: &[u8], zero_allowed: bool) {
+        let fill = self
+            .width
+            .unwrap_or(0)
+            .saturating_sub(prefix.len() + body.len());
+
+        if self.left {
+            out.extend(prefix.bytes());
+            out.extend(body);
+            out.extend(std::iter::repeat_n(b' ', fill));
+        } else if self.zero && zero_allowed {
+            out.extend(prefix.bytes());
+            out.extend(std::iter::repeat_n(b'0', fill));
+            out.extend(body);
+        } else {
+            out.extend(std::iter::repeat_n(b' ', fill));
+            out.extend(prefix.bytes());
+            out.extend(body);
+        }
+    }
+
+    fn write_integer(&self, out: &mut Vec<u8>, value: i128) {
+        let magnitude = value.unsigned_abs();
+        let (digits, base_prefix) = match self.conversion {
+            b'o' => (format!("{magnitude:o}"), if self.alt { "0" } else { "" }),
+            b'x' => (format!("{magnitude:x}"), if self.alt { "0x" } else { "" }),
+            b'X' => (format!("{magnitude:X}"), if self.alt { "0X" } else { "" }),
+            _ => (magnitude.to_string(), ""),
+        };
+
+        // The precision of an integer is its minimum number of digits
+        let digits = match self.precision {
+            Some(precision) if digits.len() < precision => {
+                format!("{}{digits}", "0".repeat(precision - digits.len()))
+            }
+            _ => digits,
+        };
+
+        let prefix = format!("{}{base_prefix}", self.sign(value < 0));
+        self.pad(out, &prefix, digits.as_bytes(), self.precision.is_none());
+    }
+
+    fn write_float(&self, out: &mut Vec<u8>, value: f64) {
+        let precision = self.precision.unwrap_or(6);
+        let magnitude = value.abs();
+
+        let body = if value.is_nan() {
+            "nan".to_string()
+        } else if value.is_infinite() {
+            "inf".to_string()
+        } else {
+            match self.conversion {
+                b'f' => format!("{magnitude:.precision$}"),
+                b'e' | b'E' => format_exponential(magnitude, precision),
+                _ => format_general(magnitude, precision, self.alt),
+            }
+        };
+        let body = if self.conversion.is_ascii_uppercase() {
+            body.to_ascii_uppercase()
+        } else {
+            body
+        };
+
+        let sign = self.sign(value.is_sign_negative() && !value.is_nan());
+        self.pad(out, sign, body.as_bytes(), value.is_finite());
+    }
+
+    //------------ Reading ---------------------------------------------
+
+    fn scan(&self, input: &mut Input<'_>) -> Result<ScanfValue, Error> {
+        match self.conversion {
+            b'c' => input
+                .take(1)
+                .map(|c| ScanfValue::Text(c.to_vec()))
+                .ok_or_else(|| input.mismatch()),
+
+            b's' => {
+                input.skip_whitespace();
+                let max = if self.alt { None } else { self.width };
+                let word = input.take_while(max, |c| !c.is_ascii_whitespace());
+                if word.is_empty() {
+                    return Err(input.mismatch());
+                }
+                Ok(ScanfValue::Text(word.to_vec()))
+            }
+
+            b't' | b'T' => Ok(ScanfValue::Text(input.rest().to_vec())),
+
+            b'b' => self.scan_block(input),
+
+            b'y' => {
+                let size = self.modifier.element_size();
+                let data = match self.width {
+                    Some(count) if !self.alt => {
+                        input.take(count * size).ok_or_else(|| input.mismatch())?
+                    }
+                    _ => input.rest(),
+                };
+                self.binary(data)
+            }
+
+            c if is_integer_conversion(c) => {
+                if self.array.is_some() {
+                    self.scan_list(input, |input| self.scan_integer(input))
+                        .map(ScanfValue::Integers)
+                } else {
+                    self.scan_integer(input).map(ScanfValue::Int)
+                }
+            }
+
+            _ => {
+                if self.array.is_some() {
+                    self.scan_list(input, Self::scan_float)
+                        .map(ScanfValue::Floats)
+                } else {
+                    Self::scan_float(input).map(ScanfValue::Float)
+                }
+            }
+        }
+    }
+
+    fn binary(&self, data: &[u8]) -> Result<ScanfValue, Error> {
+        if !data.len().is_multiple_of(self.modifier.element_size()) {
+            return Err(invalid_data(format!(
+                "{} bytes of binary data is not a whole number of {}-byte elements",
+                data.len(),
+                self.modifier.element_size()
+            )));
+        }
+
+        Ok(ScanfValue::Binary {
+            data: data.to_vec(),
+            little_endian: self.little_endian,
+        })
+    }
+
+    /// Read an IEEE 488.2 block (`#<digits><length><data>`, or `#0<data>` up to the end of the message)
+    fn scan_block(&self, input: &mut Input<'_>) -> Result<ScanfValue, Error> {
+        input.skip_whitespace();
+        if input.take(1) != Some(b"#".as_slice()) {
+            return Err(input.mismatch());
+        }
+
+        let digits = input
+            .take(1)
+            .and_then(|d| char::from(d[0]).to_digit(10))
+            .ok_or_else(|| input.mismatch())?;
+        let data = if digits == 0 {
+            let data = input.rest();
+            data.strip_suffix(b"\n").unwrap_or(data)
+        } else {
+            let length = input
+                .take(digits as usize)
+                .and_then(|length| std::str::from_utf8(length).ok()?.parse::<usize>().ok())
+                .ok_or_else(|| input.mismatch())?;
+            input
+                .take(length)
+                .ok_or_else(|| invalid_data(format!("Block of {length} bytes is truncated")))?
+        };
+
+        self.binary(data)
+    }
+
+    fn scan_list<T>(
+        &self,
+        input: &mut Input<'_>,
+        scan: impl Fn(&mut Input<'_>) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let mut values = vec![scan(input)?];
+        loop {
+            if self.array == Some(Count::Fixed(values.len())) {
+                break;
+            }
+
+            let position = input.position;
+            input.skip_whitespace();
+            if input.take(1) != Some(b",".as_slice()) {
+                input.position = position;
+                break;
+            }
+            values.push(scan(input)?);
+        }
+
+        match self.array {
+            Some(Count::Fixed(n)) if n != values.len() => Err(invalid_data(format!(
+                "Expected {n} elements, got {}",
+                values.len()
+            ))),
+            _ => Ok(values),
+        }
+    }
+
+    fn scan_integer(&self, input: &mut Input<'_>) -> Result<i128, Error> {
+        input.skip_whitespace();
+        let start = input.position;
+
+        let negative = match input.peek() {
+            Some(b'-') => {
+                input.position += 1;
+                true
+            }
+            Some(b'+') => {
+                input.position += 1;
+                false
+            }
+            _ => false,
+        };
+
+        let hex_prefix = input.remaining().len() > 2
+            && input.remaining()[0] == b'0'
+            && matches!(input.remaining()[1], b'x' | b'X')
+            && input.remaining()[2].is_ascii_hexdigit();
+        let radix = match self.conversion {
+            b'x' | b'X' => 16,
+            b'o' => 8,
+            b'i' if hex_prefix => 16,
+            b'i' if input.peek() == Some(b'0') => 8,
+            _ => 10,
+        };
+        if radix == 16 && hex_prefix {
+            input.position += 2;
+        }
+
+        let digits = input.take_while(None, |c| char::from(c).is_digit(radix));
+        let magnitude = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| i128::from_str_radix(digits, radix).ok())
+            .ok_or_else(|| {
+                input.position = start;
+                input.mismatch()
+            })?;
+
+        Ok(if negative { -magnitude } else { magnitude })
+    }
+
+    fn scan_float(input: &mut Input<'_>) -> Result<f64, Error> {
+        input.skip_whitespace();
+        let start = input.position;
+
+        input.take_while(Some(1), |c| c == b'+' || c == b'-');
+        input.take_while(None, |c| c.is_ascii_digit());
+        if input.peek() == Some(b'.') {
+            input.position += 1;
+            input.take_while(None, |c| c.is_ascii_digit());
+        }
+
+        // Only consume the exponent if it is complete
+        let mantissa_end = input.position;
+        if matches!(input.peek(), Some(b'e' | b'E')) {
+            input.position += 1;
+            input.take_while(Some(1), |c| c == b'+' || c == b'-');
+            if input.take_while(None, |c| c.is_ascii_digit()).is_empty() {
+                input.position = mantissa_end;
+            }
+        }
+
+        let data = input.data;
+        let text = &data[start..input.position];
+        std::str::from_utf8(text)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| {
+                input.position = start;
+                input.mismatch()
+            })
+    }
+}
+
+/// Format a float in the style of C's `%e`
+fn format_exponential(magnitude: f64, precision: usize) -> String {
+    let formatted = format!("{magnitude:.precision$e}");
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    format!(
+        "{mantissa}e{}{:02}",
+        if exponent < 0 { '-' } else { '+' },
+        exponent.unsigned_abs()
+    )
+}
+
+/// Format a float in the style of C's `%g`
+fn format_general(magnitude: f64, precision: usize, alt: bool) -> String {
+    let precision = precision.max(1);
+    let exponent: i32 = format!("{magnitude:.decimals$e}", decimals = precision - 1)
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse().ok())
+        .unwrap_or(0);
+
+    let significant = i32::try_from(precision).unwrap_or(i32::MAX);
+    let formatted = if (-4..significant).contains(&exponent) {
+        let decimals = usize::try_from(significant - 1 - exponent).unwrap_or(0);
+        format!("{magnitude:.decimals$}")
+    } else {
+        format_exponential(magnitude, precision - 1)
+    };
+
+    if alt {
+        return formatted;
+    }
+
+    // Strip the trailing zeros of the mantissa
+    let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap_or(formatted.len()));
+    if !mantissa.contains('.') {
+        return formatted;
+    }
+    let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+    format!("{mantissa}{exponent}")
+}
+
+/// The message being parsed by `scanf!`
+struct Input<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+impl<'a> Input<'a> {
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let taken = self.remaining().get(..n)?;
+        self.position += n;
+        Some(taken)
+    }
+
+    fn take_while(&mut self, max: Option<usize>, f: impl Fn(u8) -> bool) -> &'a [u8] {
+        let remaining = self.remaining();
+        let max = max.unwrap_or(usize::MAX).min(remaining.len());
+        let n = remaining[..max].iter().take_while(|&&c| f(c)).count();
+        self.position += n;
+        &remaining[..n]
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = self.remaining();
+        self.position = self.data.len();
+        rest
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(None, |c| c.is_ascii_whitespace());
+    }
+
+    fn mismatch(&self) -> Error {
+        invalid_data(format!(
+            "Response does not match the format at byte {}: {:?}",
+            self.position,
+            String::from_utf8_lossy(&self.remaining()[..self.remaining().len().min(32)])
+        ))
+    }
+}
+
+//------------ Errors --------------------------------------------------
+
+fn invalid_format(description: impl Into<String>) -> Error {
+    Error {
+        status: ErrorType::InvFmt,
+        description: Some(description.into()),
+    }
+}
+
+fn unsupported_format(what: impl std::fmt::Display) -> Error {
+    Error {
+        status: ErrorType::NsupFmt,
+        description: Some(format!("{what} is not supported")),
+    }
+}
+
+fn invalid_data(description: impl Into<String>) -> Error {
+    Error {
+        status: ErrorType::InvFmt,
+        description: Some(description.into()),
+    }
+}
+
+fn out_of_range(value: i128) -> Error {
+    Error {
+        status: ErrorType::InvParameter,
+        description: Some(format!("Value {value} is out of range for the argument")),
+    }
+}
+
+fn mismatched_target() -> Error {
+    Error {
+        status: ErrorType::InvParameter,
+        description: Some("Value does not match the type of the argument".to_string()),
+    }
+}
+
+//------------ Entry points --------------------------------------------
+
+fn check_count(segments: &[Segment<'_>], args: usize) -> Result<(), Error> {
+    let expected = conversions(segments).count();
+    if expected == args {
+        Ok(())
+    } else {
+        Err(invalid_format(format!(
+            "Format expects {expected} arguments, got {args}"
+        )))
+    }
+}
+
+fn check_targets(segments: &[Segment<'_>], targets: &[&mut dyn ScanfTarget]) -> Result<(), Error> {
+    check_count(segments, targets.len())?;
+    conversions(segments)
+        .zip(targets)
+        .try_for_each(|(spec, target)| spec.check(target.kind(), false))
+}
+
+/// Format data like `viSPrintf`, without any I/O
+///
+/// # Errors
+/// Will return an error if the format is invalid, or does not match the arguments
+pub fn sprintf(format: &str, args: &[&dyn PrintfArg]) -> Result<Vec<u8>, Error> {
+    let segments = parse_format(format)?;
+    check_count(&segments, args.len())?;
+
+    let values: Vec<_> = args.iter().map(|arg| arg.printf_value()).collect();
+    conversions(&segments)
+        .zip(&values)
+        .try_for_each(|(spec, value)| spec.check(value.kind(), true))?;
+
+    let mut out = vec![];
+    let mut values = values.iter();
+    for segment in &segments {
+        match segment {
+            Segment::Literal(literal) => out.extend_from_slice(literal),
+            Segment::Conversion(spec) => {
+                if let Some(value) = values.next() {
+                    spec.write(&mut out, value)?;
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Parse data like `viSScanf`, without any I/O
+///
+/// # Errors
+/// Will return an error if the format is invalid, does not match the targets, or the data does not match the format
+pub fn sscanf(
+    input: &[u8],
+    format: &str,
+    targets: &mut [&mut dyn ScanfTarget],
+) -> Result<(), Error> {
+    let segments = parse_format(format)?;
+    check_targets(&segments, targets)?;
+    scan_segments(input, &segments, targets)
+}
+
+fn scan_segments(
+    input: &[u8],
+    segments: &[Segment<'_>],
+    targets: &mut [&mut dyn ScanfTarget],
+) -> Result<(), Error> {
+    let mut input = Input {
+        data: input,
+        position: 0,
+    };
+    let mut targets = targets.iter_mut();
+
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) => {
+                for &c in *literal {
+                    if c.is_ascii_whitespace() {
+                        input.skip_whitespace();
+                    } else if input.peek() == Some(c) {
+                        input.position += 1;
+                    } else {
+                        return Err(input.mismatch());
+                    }
+                }
+            }
+            Segment::Conversion(spec) => {
+                let value = spec.scan(&mut input)?;
+                if let Some(target) = targets.next() {
+                    target.assign(value)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Session {
+    /// Get a handle to the formatted I/O buffers, implementing `Read` and `Write`
+    ///
+    /// See `FormattedIo`
+    ///
+    /// # Errors
+    /// Will return an error if the buffer attributes cannot be read
+    pub fn formatted_io(&mut self) -> Result<FormattedIo<'_>, Error> {
+        FormattedIo::new(self)
+    }
+
+    /// Format data and write it to the device, like `viPrintf`
+    ///
+    /// The data is written to the formatted I/O write buffer, which is flushed if the data ends with `\n`.
+    /// Prefer the `printf!` macro.
+    ///
+    /// # Errors
+    /// Will return an error if the format does not match the arguments, or the data cannot be written
+    pub fn printf(&self, format: &str, args: &[&dyn PrintfArg]) -> Result<(), Error> {
+        let data = sprintf(format, args)?;
+        self.buffered_write(&data)?;
+        if data.ends_with(b"\n") {
+            self.flush_write_buffer(false)?;
+        }
+        Ok(())
+    }
+
+    /// Read a response message and parse it, like `viScanf`
+    ///
+    /// The targets are checked against the format before anything is read.
+    /// Prefer the `scanf!` macro.
+    ///
+    /// # Errors
+    /// Will return an error if the format does not match the targets, the read fails, or the response does not match the format
+    pub fn scanf(&self, format: &str, targets: &mut [&mut dyn ScanfTarget]) -> Result<(), Error> {
+        let segments = parse_format(format)?;
+        check_targets(&segments, targets)?;
+
+        let message = self.buffered_read_message()?;
+        scan_segments(&message, &segments, targets)
+    }
+
+    /// Write a formatted command, then read and parse the response, like `viQueryf`
+    ///
+    /// Both formats are checked before anything is written. The write buffer is always flushed before reading.
+    /// Other threads cannot use the session between the write and the read.
+    /// Prefer the `queryf!` macro.
+    ///
+    /// # Errors
+    /// Will return an error if a format does not match its arguments, the I/O fails, or the response does not match the format
+    pub fn queryf(
+        &self,
+        write_format: &str,
+        args: &[&dyn PrintfArg],
+        read_format: &str,
+        targets: &mut [&mut dyn ScanfTarget],
+    ) -> Result<(), Error> {
+        let segments = parse_format(read_format)?;
+        check_targets(&segments, targets)?;
+
+        let data = sprintf(write_format, args)?;
+        // The clone shares the I/O lock and buffers of this session
+        let message = self.clone().transaction(|tx| {
+            tx.buffered_write(&data)?;
+            tx.flush_write_buffer(false)?;
+            tx.buffered_read_message()
+        })?;
+        scan_segments(&message, &segments, targets)
+    }
+
+    fn buffered_write(&self, data: &[u8]) -> Result<(), Error> {
+        let vi = self.session_id();
+        let mut written = 0;
+        while written < data.len() {
+            let remaining = &data[written..];
+            let mut count = 0;
+            Error::wrap_binding(Some(vi), || unsafe {
+                bindings::viBufWrite(
+                    vi,
+                    remaining.as_ptr(),
+                    u32::try_from(remaining.len()).unwrap_or(u32::MAX),
+                    &raw mut count,
+                )
+            })?;
+
+            if count == 0 {
+                return Err(Error::from_msg(
+                    "Formatted I/O write buffer did not accept any data",
+                ));
+            }
+            written += count as usize;
+        }
+        Ok(())
+    }
+
+    /// Read from the formatted I/O read buffer up to END or the termination character
+    fn buffered_read_message(&self) -> Result<Vec<u8>, Error> {
+        const CHUNK: usize = 1024;
+
+        let vi = self.session_id();
+        let mut message = vec![];
+        let mut chunk = [0u8; CHUNK];
+        loop {
+            let mut count = 0;
+            let status = Error::wrap_binding_status(Some(vi), || unsafe {
+                bindings::viBufRead(vi, chunk.as_mut_ptr(), CHUNK as u32, &raw mut count)
+            })?;
+
+            message.extend_from_slice(&chunk[..count as usize]);
+            if status != bindings::VI_SUCCESS_MAX_CNT as i32 {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+/// A handle to the formatted I/O buffers of a session
+///
+/// `Write` goes through the formatted write buffer (`viBufWrite`) and `Read` through the formatted read buffer (`viBufRead`),
+/// so raw data and `printf!`/`scanf!` can be interleaved without reordering.
+///
+/// The write buffer is flushed according to the `WrBufOperMode` attribute:
+/// - `OnAccess`: after every write
+/// - `OnFull`: when the buffer fills up, when `flush` is called, and when the handle is dropped
+#[derive(Debug)]
+pub struct FormattedIo<'a> {
+    session: &'a mut Session,
+    mode: WrBufOperModeType,
+    capacity: usize,
+    buffered: usize,
+}
+
+impl<'a> FormattedIo<'a> {
+    /// Create a handle, reading the write buffer mode and size from the session
+    ///
+    /// # Errors
+    /// Will return an error if the attributes cannot be read
+    pub fn new(session: &'a mut Session) -> Result<Self, Error> {
+        let mode = session.get_attribute::<WrBufOperMode>()?;
+        let capacity = session.get_attribute::<WrBufSize>()? as usize;
+        Ok(Self {
+            session,
+            mode,
+            capacity,
+            buffered: 0,
+        })
+    }
+
+    /// Get the flushing mode of the write buffer
+    #[must_use]
+    pub fn mode(&self) -> WrBufOperModeType {
+        self.mode
+    }
+
+    /// Set the flushing mode of the write buffer
+    ///
+    /// Data already buffered is flushed first
+    ///
+    /// # Errors
+    /// Will return an error if the buffer cannot be flushed, or the attribute cannot be set
+    pub fn set_mode(&mut self, mode: WrBufOperModeType) -> Result<(), Error> {
+        self.flush_buffer()?;
+        self.session.set_attribute::<WrBufOperMode>(mode)?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// Get the size of the write buffer
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of bytes written to the write buffer and not yet sent to the device
+    #[must_use]
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    /// Format data and write it to the write buffer, like `printf!`
+    ///
+    /// Unlike `Session::printf`, the buffer is only flushed according to the buffer mode.
+    ///
+    /// # Errors
+    /// Will return an error if the format does not match the arguments, or the data cannot be written
+    pub fn printf(&mut self, format: &str, args: &[&dyn PrintfArg]) -> Result<(), Error> {
+        let data = sprintf(format, args)?;
+        std::io::Write::write_all(self, &data)?;
+        Ok(())
+    }
+
+    /// Flush the write buffer, then get the session for unbuffered I/O
+    ///
+    /// Data left in the read buffer is not discarded, and will be returned by the next buffered read.
+    ///
+    /// # Errors
+    /// Will return an error if the buffer cannot be flushed
+    pub fn raw(&mut self) -> Result<&mut Session, Error> {
+        self.flush_buffer()?;
+        Ok(self.session)
+    }
+
+    /// Discard the data in the write buffer without sending it
+    ///
+    /// # Errors
+    /// Will return an error if the buffer cannot be discarded
+    pub fn discard(&mut self) -> Result<(), Error> {
+        self.session.flush_write_buffer(true)?;
+        self.buffered = 0;
+        Ok(())
+    }
+
+    /// Send the data in the write buffer to the device
+    ///
+    /// # Errors
+    /// Will return an error if the buffer cannot be flushed
+    pub fn flush_buffer(&mut self) -> Result<(), Error> {
+        self.session.flush_write_buffer(false)?;
+        self.buffered = 0;
+        Ok(())
+    }
+}
+
+impl std::io::Write for FormattedIo<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let vi = self.session.session_id();
+        let mut written = 0;
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viBufWrite(
+                vi,
+                buf.as_ptr(),
+                u32::try_from(buf.len()).unwrap_or(u32::MAX),
+                &raw mut written,
+            )
+        })?;
+
+        // VISA sends the buffer on its own whenever it fills up
+        self.buffered += written as usize;
+        if self.capacity > 0 {
+            self.buffered %= self.capacity;
+        }
+
+        if self.mode == WrBufOperModeType::OnAccess {
+            self.flush_buffer()?;
+        }
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(self.flush_buffer()?)
+    }
+}
+
+impl std::io::Read for FormattedIo<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let vi = self.session.session_id();
+        let mut read = 0;
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viBufRead(
+                vi,
+                buf.as_mut_ptr(),
+                u32::try_from(buf.len()).unwrap_or(u32::MAX),
+                &raw mut read,
+            )
+        })?;
+        Ok(read as usize)
+    }
+}
+
+impl Drop for FormattedIo<'_> {
+    fn drop(&mut self) {
+        if self.buffered > 0 {
+            self.flush_buffer().ok();
+        }
+    }
+}
+
+/// Formats data and writes it to the session device, like `viPrintf`
+///
+/// Arguments are checked against the format before anything is written; see the `formatted` module for the supported conversions.
+///
+/// ```ignore
+/// printf!(&session, "SOUR:VOLT %.3f;CURR %.3f\n", 12.0, 0.5)?;
+/// printf!(&session, "TRAC:DATA %hb\n", &samples)?;
+/// ```
+#[macro_export]
+macro_rules! printf {
+    ($session:expr, $format:expr $(, $arg:expr)* $(,)?) => {
+        $crate::Session::printf(
+            $session,
+            $format,
+            &[$(&$arg as &dyn $crate::formatted::PrintfArg),*],
+        )
+    };
+}
+
+/// Reads a response message from the session device and parses it, like `viScanf`
+///
+/// Targets are places (variables or fields); they are checked against the format before anything is read.
+/// See the `formatted` module for the supported conversions.
+///
+/// ```ignore
+/// let mut voltage = 0.0f64;
+/// scanf!(&session, "%lf", voltage)?;
+/// ```
+#[macro_export]
+macro_rules! scanf {
+    ($session:expr, $format:expr $(, $arg:expr)* $(,)?) => {
+        $crate::Session::scanf(
+            $session,
+            $format,
+            &mut [$(&mut $arg as &mut dyn $crate::formatted::ScanfTarget),*],
+        )
+    };
+}
+
+/// Writes a formatted command and parses the response, like `viQueryf`
+///
+/// The write format and arguments are separated from the read format and targets by a `;`
+///
+/// ```ignore
+/// let mut reading = 0.0f64;
+/// queryf!(&session, "MEAS:VOLT? (@%d)\n", channel; "%lf", reading)?;
+/// ```
+#[macro_export]
+macro_rules! queryf {
+    (
+        $session:expr, $write_format:expr $(, $write_arg:expr)*;
+        $read_format:expr $(, $read_arg:expr)* $(,)?
+    ) => {
+        $crate::Session::queryf(
+            $session,
+            $write_format,
+            &[$(&$write_arg as &dyn $crate::formatted::PrintfArg),*],
+            $read_format,
+            &mut [$(&mut $read_arg as &mut dyn $crate::formatted::ScanfTarget),*],
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_sprintf() {
+        let out = sprintf("VOLT %.3f;CURR %5.1e\n", &[&12.0, &0.5]).unwrap();
+        assert_eq!(out, b"VOLT 12.000;CURR 5.0e-01\n");
+
+        let out = sprintf(
+            "%d|%-4d|%04x|%#X|%+i|%%|%c",
+            &[&-7, &3, &255u8, &255, &4, &'A'],
+        )
+        .unwrap();
+        assert_eq!(out, b"-7|3   |00ff|0XFF|+4|%|A");
+
+        let out = sprintf("%g %g %g %G", &[&0.0001, &123_456.0, &1e10, &1.5e-7]).unwrap();
+        assert_eq!(out, b"0.0001 123456 1e+10 1.5E-07");
+
+        let out = sprintf("%s,%.2s,%5s", &[&"abc", &"abc", &b"ab".as_slice()]).unwrap();
+        assert_eq!(out, b"abc,ab,   ab");
+
+        let out = sprintf("%,#d;%,2.1f", &[&[1i16, -2, 3], &vec![0.25f64, 1.0, 2.0]]).unwrap();
+        assert_eq!(out, b"1,-2,3;0.2,1.0");
+    }
+
+    #[test]
+    fn test_sprintf_binary() {
+        let out = sprintf("%hb", &[&[1i16, -2]]).unwrap();
+        assert_eq!(out, b"#14\x00\x01\xFF\xFE");
+
+        let out = sprintf("%!olhy", &[&[1u16, 0x0203]]).unwrap();
+        assert_eq!(out, b"\x01\x00\x03\x02");
+
+        let out = sprintf("%2b", &[&b"abc"]).unwrap();
+        assert_eq!(out, b"#12ab");
+
+        let out = sprintf("%Zy", &[&[1.0f64]]).unwrap();
+        assert_eq!(out, 1.0f64.to_be_bytes());
+
+        assert!(sprintf("%b", &[&[256i32]]).is_err());
+    }
+
+    #[test]
+    fn test_sprintf_checks() {
+        let error = sprintf("%d", &[&"text"]).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvFmt);
+
+        let error = sprintf("%d %d", &[&1]).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvFmt);
+
+        let error = sprintf("%zb", &[&[1i32]]).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvFmt);
+
+        let error = sprintf("%[a-z]", &[&"text"]).unwrap_err();
+        assert_eq!(error.status, ErrorType::NsupFmt);
+
+        assert!(sprintf("%t", &[&"text"]).is_err());
+        assert!(sprintf("%", &[]).is_err());
+    }
+
+    #[test]
+    fn test_sscanf() {
+        let (mut a, mut b, mut c) = (0i32, 0.0f64, String::new());
+        sscanf(
+            b"  42, +1.5E+00 OK\n",
+            "%d,%lf %s",
+            &mut [&mut a, &mut b, &mut c],
+        )
+        .unwrap();
+        assert_eq!((a, b, c.as_str()), (42, 1.5, "OK"));
+
+        let (mut x, mut rest) = (0u8, Vec::<u8>::with_capacity(16));
+        sscanf(b"0x1f trailing data", "%x%t", &mut [&mut x, &mut rest]).unwrap();
+        assert_eq!(x, 0x1f);
+        assert_eq!(rest, b" trailing data");
+
+        let mut values: Vec<i32> = vec![];
+        sscanf(b"1,-2, 3\n", "%,#d", &mut [&mut values]).unwrap();
+        assert_eq!(values, [1, -2, 3]);
+
+        let mut values: Vec<f64> = vec![];
+        sscanf(b"1.5,2e3", "%,2lf", &mut [&mut values]).unwrap();
+        assert_eq!(values, [1.5, 2000.0]);
+        assert!(sscanf(b"1.5", "%,2lf", &mut [&mut values]).is_err());
+    }
+
+    #[test]
+    fn test_sscanf_binary() {
+        let mut samples: Vec<i16> = vec![];
+        sscanf(b"#14\x00\x01\xFF\xFE\n", "%hb", &mut [&mut samples]).unwrap();
+        assert_eq!(samples, [1, -2]);
+
+        let mut bytes: Vec<u8> = vec![];
+        sscanf(b"#0abc\n", "%b", &mut [&mut bytes]).unwrap();
+        assert_eq!(bytes, b"abc");
+
+        let mut values: Vec<f32> = vec![];
+        sscanf(&2.5f32.to_le_bytes(), "%!olzy", &mut [&mut values]).unwrap();
+        assert_eq!(values, [2.5]);
+
+        assert!(sscanf(b"#15\x00\x01", "%hb", &mut [&mut samples]).is_err());
+    }
+
+    #[test]
+    fn test_sscanf_checks() {
+        let mut value = 0u8;
+        let error = sscanf(b"300", "%d", &mut [&mut value]).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvParameter);
+
+        let error = sscanf(b"abc", "%d", &mut [&mut value]).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvFmt);
+
+        // Element sizes of binary data must match the target
+        let mut samples: Vec<i32> = vec![];
+        let error = sscanf(b"#14\x00\x01\xFF\xFE", "%hb", &mut [&mut samples]).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvFmt);
+
+        let mut text = String::new();
+        assert!(sscanf(b"42", "%d", &mut [&mut text]).is_err());
+        assert!(sscanf(b"42", "%d %d", &mut [&mut value]).is_err());
+    }
+
+    #[test]
+    fn test_formatted_io() {
+        let mut session = get_local_device();
+        let mut io = session.formatted_io().unwrap();
+        io.set_mode(WrBufOperModeType::OnFull).unwrap();
+
+        io.write_all(b"*IDN").unwrap();
+        io.printf("%s\n", &[&"?"]).unwrap();
+        assert_eq!(io.buffered(), 6);
+
+        io.flush().unwrap();
+        assert_eq!(io.buffered(), 0);
+
+        let mut response = [0u8; 256];
+        let len = io.read(&mut response).unwrap();
+        assert!(len > 0);
+    }
+}
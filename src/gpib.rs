@@ -0,0 +1,400 @@
+//! GPIB bus control
+//!
+//! `GpibInterface` wraps a GPIB INTFC session, to control the bus itself rather than a single device: pulse IFC,
+//! drive the REN and ATN lines, pass control to another controller, and send command bytes with ATN asserted.
+//! `GpibCommand` builds these command sequences from the IEEE 488.1 multiline messages:
+//! ```ignore
+//! let bus = GpibInterface::new(Session::new(&rm, "GPIB0::INTFC", SessionOptions::default())?)?;
+//! bus.send_ifc()?;
+//!
+//! // Trigger the devices at addresses 5 and 7 at the same time
+//! bus.send_command(GpibCommand::new().unl().mta(0).mla(5).mla(7).get())?;
+//! ```
+//!
+//! The states of the bus lines are available as the `attribute::gpib` attributes of the session.
+//!
+//! `Session::control_ren` puts a single instrument in remote or local mode, on GPIB and USB488 INSTR sessions.
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "Needed for compatibility with the VISA library"
+)]
+use crate::{
+    attribute::{misc::InterfaceType, misc::IntfType, rsrc::RsrcClass},
+    bindings,
+    error::Error,
+    Session,
+};
+
+/// Highest primary or secondary GPIB address
+const MAX_ADDRESS: u8 = 30;
+
+/// Actions on the REN (Remote `ENable`) line, for `Session::control_ren` and `GpibInterface::control_ren`
+///
+/// The modes addressing the device of the session only apply to INSTR sessions; INTFC sessions reject them.
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RenMode {
+    /// Deassert REN
+    Deassert = bindings::VI_GPIB_REN_DEASSERT,
+
+    /// Assert REN
+    Assert = bindings::VI_GPIB_REN_ASSERT,
+
+    /// Send GTL to all devices, and deassert REN
+    DeassertGtl = bindings::VI_GPIB_REN_DEASSERT_GTL,
+
+    /// Assert REN and address the device of the session (INSTR sessions only)
+    AssertAddress = bindings::VI_GPIB_REN_ASSERT_ADDRESS,
+
+    /// Send LLO to all devices
+    AssertLlo = bindings::VI_GPIB_REN_ASSERT_LLO,
+
+    /// Address the device of the session and send LLO, putting it in remote with lockout (INSTR sessions only)
+    AssertAddressLlo = bindings::VI_GPIB_REN_ASSERT_ADDRESS_LLO,
+
+    /// Send GTL to the device of the session (INSTR sessions only)
+    AddressGtl = bindings::VI_GPIB_REN_ADDRESS_GTL,
+}
+
+/// Actions on the ATN (`ATtentioN`) line, for `GpibInterface::control_atn`
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AtnMode {
+    /// Deassert ATN
+    Deassert = bindings::VI_GPIB_ATN_DEASSERT,
+
+    /// Assert ATN synchronously, without corrupting a transfer in progress
+    Assert = bindings::VI_GPIB_ATN_ASSERT,
+
+    /// Deassert ATN, and enter shadow handshake
+    DeassertHandshake = bindings::VI_GPIB_ATN_DEASSERT_HANDSHAKE,
+
+    /// Assert ATN immediately, aborting any transfer in progress
+    AssertImmediate = bindings::VI_GPIB_ATN_ASSERT_IMMEDIATE,
+}
+
+/// A sequence of GPIB command bytes, sent with ATN asserted by `GpibInterface::send_command`
+///
+/// Each method appends one IEEE 488.1 multiline message. The addressing methods panic on addresses above 30.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GpibCommand(Vec<u8>);
+impl GpibCommand {
+    const GTL: u8 = 0x01;
+    const SDC: u8 = 0x04;
+    const PPC: u8 = 0x05;
+    const GET: u8 = 0x08;
+    const TCT: u8 = 0x09;
+    const LLO: u8 = 0x11;
+    const DCL: u8 = 0x14;
+    const PPU: u8 = 0x15;
+    const SPE: u8 = 0x18;
+    const SPD: u8 = 0x19;
+    const LISTEN: u8 = 0x20;
+    const UNL: u8 = 0x3F;
+    const TALK: u8 = 0x40;
+    const UNT: u8 = 0x5F;
+    const SECONDARY: u8 = 0x60;
+
+    /// An empty command sequence
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The command bytes
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Append a raw command byte
+    #[must_use]
+    pub fn raw(mut self, byte: u8) -> Self {
+        self.0.push(byte);
+        self
+    }
+
+    fn address(self, group: u8, address: u8) -> Self {
+        assert!(address <= MAX_ADDRESS, "Invalid GPIB address {address}");
+        self.raw(group | address)
+    }
+
+    /// My Talk Address: address the device at `address` to talk
+    ///
+    /// # Panics
+    /// Panics if `address` is above 30
+    #[must_use]
+    pub fn mta(self, address: u8) -> Self {
+        self.address(Self::TALK, address)
+    }
+
+    /// My Listen Address: address the device at `address` to listen
+    ///
+    /// # Panics
+    /// Panics if `address` is above 30
+    #[must_use]
+    pub fn mla(self, address: u8) -> Self {
+        self.address(Self::LISTEN, address)
+    }
+
+    /// My Secondary Address: the secondary address of the device addressed by the previous MTA or MLA
+    ///
+    /// # Panics
+    /// Panics if `address` is above 30
+    #[must_use]
+    pub fn msa(self, address: u8) -> Self {
+        self.address(Self::SECONDARY, address)
+    }
+
+    /// Untalk: unaddress the talker
+    #[must_use]
+    pub fn unt(self) -> Self {
+        self.raw(Self::UNT)
+    }
+
+    /// Unlisten: unaddress all listeners
+    #[must_use]
+    pub fn unl(self) -> Self {
+        self.raw(Self::UNL)
+    }
+
+    /// Selected Device Clear: clear the addressed listeners
+    #[must_use]
+    pub fn sdc(self) -> Self {
+        self.raw(Self::SDC)
+    }
+
+    /// Device Clear: clear all devices
+    #[must_use]
+    pub fn dcl(self) -> Self {
+        self.raw(Self::DCL)
+    }
+
+    /// Group Execute Trigger: trigger the addressed listeners
+    #[must_use]
+    pub fn get(self) -> Self {
+        self.raw(Self::GET)
+    }
+
+    /// Local Lockout: disable the local controls of all devices
+    #[must_use]
+    pub fn llo(self) -> Self {
+        self.raw(Self::LLO)
+    }
+
+    /// Go To Local: return the addressed listeners to local control
+    #[must_use]
+    pub fn gtl(self) -> Self {
+        self.raw(Self::GTL)
+    }
+
+    /// Serial Poll Enable: the addressed talker sends its status byte
+    #[must_use]
+    pub fn spe(self) -> Self {
+        self.raw(Self::SPE)
+    }
+
+    /// Serial Poll Disable: end a serial poll
+    #[must_use]
+    pub fn spd(self) -> Self {
+        self.raw(Self::SPD)
+    }
+
+    /// Take Control: pass control to the addressed talker
+    #[must_use]
+    pub fn tct(self) -> Self {
+        self.raw(Self::TCT)
+    }
+
+    /// Parallel Poll Configure: the following secondary commands configure the parallel poll response of the
+    /// addressed listeners
+    #[must_use]
+    pub fn ppc(self) -> Self {
+        self.raw(Self::PPC)
+    }
+
+    /// Parallel Poll Unconfigure: disable the parallel poll response of all devices
+    #[must_use]
+    pub fn ppu(self) -> Self {
+        self.raw(Self::PPU)
+    }
+}
+impl AsRef<[u8]> for GpibCommand {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A GPIB INTFC session, controlling the bus
+#[derive(Debug, Clone)]
+pub struct GpibInterface {
+    session: Session,
+}
+impl GpibInterface {
+    /// Control the bus of a GPIB INTFC session
+    ///
+    /// # Errors
+    /// Will return an error if the session is not a GPIB INTFC session
+    pub fn new(session: Session) -> Result<Self, Error> {
+        let interface = session.get_attribute::<IntfType>()?;
+        let class = session.get_attribute::<RsrcClass>()?;
+        if interface != InterfaceType::Gpib || class != "INTFC" {
+            return Err(Error::from_msg("Not a GPIB INTFC session"));
+        }
+        Ok(Self { session })
+    }
+
+    /// The INTFC session
+    #[must_use]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Get back the INTFC session
+    #[must_use]
+    pub fn into_session(self) -> Session {
+        self.session
+    }
+
+    /// Pulse the IFC (`InterFace` Clear) line, making the interface the controller in charge
+    ///
+    /// # Errors
+    /// Will return an error if the interface is not the system controller
+    pub fn send_ifc(&self) -> Result<(), Error> {
+        let vi = self.session.session_id();
+        Error::wrap_binding(Some(vi), || unsafe { bindings::viGpibSendIFC(vi) })
+    }
+
+    /// Control the REN (Remote `ENable`) line, and the remote/local state of the devices
+    ///
+    /// Only `Deassert`, `Assert`, `DeassertGtl` and `AssertLlo` apply to the bus; use `Session::control_ren` on
+    /// the INSTR session of a device to address it.
+    ///
+    /// # Errors
+    /// Will return an error if the interface is not the system controller, the mode addresses a device, or the
+    /// line cannot be controlled
+    pub fn control_ren(&self, mode: RenMode) -> Result<(), Error> {
+        self.session.control_ren(mode)
+    }
+
+    /// Control the ATN (`ATtentioN`) line
+    ///
+    /// # Errors
+    /// Will return an error if the interface is not the controller in charge, or the line cannot be controlled
+    pub fn control_atn(&self, mode: AtnMode) -> Result<(), Error> {
+        let vi = self.session.session_id();
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viGpibControlATN(vi, mode as u16)
+        })
+    }
+
+    /// Pass control of the bus to the controller at `primary`, and optionally `secondary`
+    ///
+    /// # Errors
+    /// Will return an error if an address is invalid, the interface is not the controller in charge, or the other
+    /// controller does not take control
+    pub fn pass_control(&self, primary: u16, secondary: Option<u16>) -> Result<(), Error> {
+        const VI_NO_SEC_ADDR: u16 = bindings::VI_NO_SEC_ADDR as u16;
+        let vi = self.session.session_id();
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viGpibPassControl(vi, primary, secondary.unwrap_or(VI_NO_SEC_ADDR))
+        })
+    }
+
+    /// Send command bytes with ATN asserted, such as a `GpibCommand`
+    ///
+    /// Returns the number of bytes sent.
+    ///
+    /// # Errors
+    /// Will return an error if the interface is not the controller in charge, or the bytes cannot be sent
+    pub fn send_command(&self, command: impl AsRef<[u8]>) -> Result<usize, Error> {
+        let command = command.as_ref();
+        let length = u32::try_from(command.len())
+            .map_err(|_| Error::new(bindings::VI_ERROR_INV_LENGTH, None))?;
+
+        let vi = self.session.session_id();
+        let mut sent = 0;
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viGpibCommand(vi, command.as_ptr(), length, &raw mut sent)
+        })?;
+        Ok(sent as usize)
+    }
+}
+
+impl Session {
+    /// Control the REN (Remote `ENable`) line, and the remote/local state of the device
+    ///
+    /// All modes apply to GPIB INSTR sessions, and to USB INSTR sessions of USB488 devices.
+    /// On GPIB INTFC sessions, prefer `GpibInterface::control_ren`.
+    ///
+    /// # Errors
+    /// Will return an error if the session does not support the mode, or the line cannot be controlled
+    pub fn control_ren(&self, mode: RenMode) -> Result<(), Error> {
+        let vi = self.session_id();
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viGpibControlREN(vi, mode as u16)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        attribute::{gpib::GpibRenState, State},
+        ResourceManager, SessionOptions,
+    };
+
+    #[test]
+    fn test_gpib_command() {
+        let command = GpibCommand::new().unl().mta(0).mla(5).msa(2).get();
+        assert_eq!(command.bytes(), [0x3F, 0x40, 0x25, 0x62, 0x08]);
+
+        let command = GpibCommand::new()
+            .dcl()
+            .sdc()
+            .llo()
+            .gtl()
+            .spe()
+            .spd()
+            .tct()
+            .ppc()
+            .ppu()
+            .unt()
+            .raw(0xFF);
+        assert_eq!(
+            command.as_ref(),
+            [0x14, 0x04, 0x11, 0x01, 0x18, 0x19, 0x09, 0x05, 0x15, 0x5F, 0xFF]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid GPIB address 31")]
+    fn test_gpib_command_address() {
+        let _ = GpibCommand::new().mla(31);
+    }
+
+    #[test]
+    fn test_gpib_interface() {
+        let rm = ResourceManager::new().unwrap();
+        let interface = std::env::var("LOCAL_GPIB_INTF").unwrap_or("GPIB0::INTFC".to_string());
+        let bus =
+            GpibInterface::new(Session::new(&rm, &interface, SessionOptions::default()).unwrap())
+                .unwrap();
+
+        bus.send_ifc().unwrap();
+        bus.control_ren(RenMode::Assert).unwrap();
+        assert_eq!(
+            bus.session().get_attribute::<GpibRenState>().unwrap(),
+            State::Asserted
+        );
+        assert_eq!(bus.send_command(GpibCommand::new().unl().unt()).unwrap(), 2);
+        bus.control_ren(RenMode::DeassertGtl).unwrap();
+
+        // Device sessions are rejected, but can be addressed on their own
+        let device = crate::get_local_device();
+        device.control_ren(RenMode::AssertAddress).unwrap();
+        device.control_ren(RenMode::AddressGtl).unwrap();
+        assert!(GpibInterface::new(device).is_err());
+    }
+}
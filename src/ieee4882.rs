@@ -0,0 +1,560 @@
+//! IEEE 488.2 common commands and status registers
+//!
+//! Typed wrappers around the mandatory and optional common commands (`*CLS`, `*ESE`, `*STB?`, ...)
+//! implemented by 488.2 compliant instruments.
+use crate::{
+    attribute::misc::TmoValue,
+    error::{Error, ErrorType},
+    event::{Event, HandlingMechanism},
+    Session,
+};
+use std::time::{Duration, Instant};
+
+impl_flags!(
+    "The IEEE 488.2 status byte, as returned by `*STB?` or a serial poll (`Session::read_status`)"
+    "Bits 0 to 3 and 7 are device defined by IEEE 488.2; SCPI instruments assign bits 2, 3 and 7."
+    StatusByte(u8) {
+        "Error/Event Available - the SCPI error queue is not empty"
+        EAV = 0x04;
+
+        "Questionable Status Bit - summary of the SCPI `STATus:QUEStionable` register"
+        QSB = 0x08;
+
+        "Message Available - the output queue contains data"
+        MAV = 0x10;
+
+        "Event Status Bit - one or more enabled bits of the standard event status register is set"
+        ESB = 0x20;
+
+        "Request Service - the device is asserting SRQ (as read by a serial poll)"
+        "Same bit as `StatusByte::MSS`."
+        RQS = 0x40;
+
+        "Operation Status Bit - summary of the SCPI `STATus:OPERation` register"
+        OSB = 0x80;
+    }
+);
+impl StatusByte {
+    /// Master Summary Status - the device has a reason to request service (as read by `*STB?`)
+    ///
+    /// Same bit as `StatusByte::RQS`, under which it is listed by `Debug`.
+    pub const MSS: Self = Self::RQS;
+}
+
+impl_flags!(
+    "The IEEE 488.2 standard event status register, as returned by `*ESR?` and configured by `*ESE`"
+    EventStatus(u8) {
+        "Operation Complete - set by `*OPC` once all pending operations are done"
+        OPC = 0x01;
+
+        "Request Control - the device wants to become the active controller"
+        RQC = 0x02;
+
+        "Query Error - data was requested from an empty output queue, or was lost"
+        QYE = 0x04;
+
+        "Device Dependent Error - an operation did not complete properly because of a device condition"
+        DDE = 0x08;
+
+        "Execution Error - a parameter was out of range, or a command could not be executed"
+        EXE = 0x10;
+
+        "Command Error - a syntax error or unrecognized command was received"
+        CME = 0x20;
+
+        "User Request - a local control was operated on the front panel"
+        URQ = 0x40;
+
+        "Power On - the device has been powered off and on since the register was last read"
+        PON = 0x80;
+    }
+);
+
+/// The parsed response to an `*IDN?` query
+///
+/// Fields reported as `0` by the instrument (meaning "not available") are kept as-is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// Manufacturer name
+    pub manufacturer: String,
+
+    /// Model name or number
+    pub model: String,
+
+    /// Serial number
+    pub serial: String,
+
+    /// Firmware or software revision
+    pub firmware: String,
+}
+impl std::str::FromStr for Identity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(Error::from_msg("Empty identification string"));
+        }
+
+        let mut fields = s.splitn(4, ',').map(|f| f.trim().to_string());
+        Ok(Self {
+            manufacturer: fields.next().unwrap_or_default(),
+            model: fields.next().unwrap_or_default(),
+            serial: fields.next().unwrap_or_default(),
+            firmware: fields.next().unwrap_or_default(),
+        })
+    }
+}
+impl std::fmt::Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.manufacturer, self.model, self.serial, self.firmware
+        )
+    }
+}
+
+/// Strategies used by `Session::wait_complete` to wait for pending operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionStrategy {
+    /// Send `*OPC?` and block on the response
+    ///
+    /// The session timeout is raised to the requested timeout for the duration of the query.
+    /// The simplest strategy, but the bus is held for the whole wait.
+    Query,
+
+    /// Send `*OPC`, then serial poll the status byte at the given interval until ESB is set
+    ///
+    /// The bus is free between polls, so other sessions can talk to their devices, and other threads can use this
+    /// session.
+    Poll(Duration),
+
+    /// Send `*OPC` and wait for the service request raised through ESB
+    ///
+    /// Uses `Event::ServiceReq` with the queue mechanism, which must not be enabled for handlers on this session.
+    ServiceRequest,
+}
+
+impl Session {
+    /// Get the parsed resource identifier (`*IDN?`)
+    ///
+    /// # Errors
+    /// Will return an error if the device does not respond to the IDN query
+    pub fn identity(&mut self) -> Result<Identity, Error> {
+        self.query("*IDN?")
+    }
+
+    /// Clear the status data structures (`*CLS`)
+    ///
+    /// Clears the event registers and the error queue, and cancels any pending `*OPC`
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn clear_status(&mut self) -> Result<(), Error> {
+        self.write_string("*CLS")
+    }
+
+    /// Set the standard event status enable register (`*ESE`)
+    ///
+    /// Enabled events are summarized in the ESB bit of the status byte
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn set_event_status_enable(&mut self, enable: EventStatus) -> Result<(), Error> {
+        self.write_string(&format!("*ESE {}", enable.bits()))
+    }
+
+    /// Read the standard event status enable register (`*ESE?`)
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn event_status_enable(&mut self) -> Result<EventStatus, Error> {
+        self.query::<u8>("*ESE?").map(EventStatus::from_bits)
+    }
+
+    /// Read and clear the standard event status register (`*ESR?`)
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn event_status(&mut self) -> Result<EventStatus, Error> {
+        self.query::<u8>("*ESR?").map(EventStatus::from_bits)
+    }
+
+    /// Set the service request enable register (`*SRE`)
+    ///
+    /// The device requests service when any enabled bit of the status byte is set.
+    /// Bit 6 (RQS/MSS) is ignored by the device.
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn set_service_request_enable(&mut self, enable: StatusByte) -> Result<(), Error> {
+        self.write_string(&format!("*SRE {}", enable.bits()))
+    }
+
+    /// Read the service request enable register (`*SRE?`)
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn service_request_enable(&mut self) -> Result<StatusByte, Error> {
+        self.query::<u8>("*SRE?").map(StatusByte::from_bits)
+    }
+
+    /// Read the status byte through the message exchange protocol (`*STB?`)
+    ///
+    /// Unlike `Session::read_status`, bit 6 is reported as MSS and is not cleared by the read
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn status_byte(&mut self) -> Result<StatusByte, Error> {
+        self.query::<u8>("*STB?").map(StatusByte::from_bits)
+    }
+
+    /// Set the OPC bit of the event status register once all pending operations are done (`*OPC`)
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn operation_complete(&mut self) -> Result<(), Error> {
+        self.write_string("*OPC")
+    }
+
+    /// Block until all pending operations are done (`*OPC?`)
+    ///
+    /// The session timeout must be long enough for the pending operations to complete
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or times out
+    pub fn wait_operation_complete(&mut self) -> Result<(), Error> {
+        match self.query::<u8>("*OPC?")? {
+            1 => Ok(()),
+            other => Err(Error::from_msg(format!(
+                "*OPC?: unexpected response {other}"
+            ))),
+        }
+    }
+
+    /// Prevent the device from executing further commands until pending operations are done (`*WAI`)
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn wait_to_continue(&mut self) -> Result<(), Error> {
+        self.write_string("*WAI")
+    }
+
+    /// Reset the device to its default state (`*RST`)
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.write_string("*RST")
+    }
+
+    /// Run the internal self-test (`*TST?`)
+    ///
+    /// Returns the device result code, where 0 means the test passed
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or times out
+    pub fn self_test(&mut self) -> Result<i32, Error> {
+        self.query("*TST?")
+    }
+
+    /// Get the list of installed options (`*OPT?`)
+    ///
+    /// A device without options reports `0`, which is returned as an empty list
+    ///
+    /// # Errors
+    /// Will return an error if the query fails
+    pub fn options(&mut self) -> Result<Vec<String>, Error> {
+        let response: String = self.query("*OPT?")?;
+        Ok(response
+            .split(',')
+            .map(str::trim)
+            .filter(|opt| !opt.is_empty() && *opt != "0")
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    /// Run the internal calibration (`*CAL?`)
+    ///
+    /// Returns the device result code, where 0 means the calibration succeeded
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or times out
+    pub fn calibrate(&mut self) -> Result<i32, Error> {
+        self.query("*CAL?")
+    }
+
+    /// Choose whether the enable registers are cleared at power on (`*PSC`)
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn set_power_on_status_clear(&mut self, clear: bool) -> Result<(), Error> {
+        self.write_string(if clear { "*PSC 1" } else { "*PSC 0" })
+    }
+
+    /// Wait until all pending operations are complete, using the given strategy
+    ///
+    /// The event status enable and service request enable registers are modified while waiting, and restored afterwards.
+    ///
+    /// Returns the standard event bits other than OPC (such as CME or EXE) read, and so cleared, while waiting.
+    /// The `Query` strategy does not read the standard event register, and returns no bits.
+    ///
+    /// On timeout, the device is cleared (and pending service requests discarded) so that the session is usable again.
+    ///
+    /// # Errors
+    /// Will return an error if the operations do not complete before the timeout, or the device cannot be accessed
+    pub fn wait_complete(
+        &mut self,
+        strategy: CompletionStrategy,
+        timeout: Duration,
+    ) -> Result<EventStatus, Error> {
+        let result = match strategy {
+            CompletionStrategy::Query => self.wait_complete_query(timeout),
+            CompletionStrategy::Poll(interval) => self.wait_complete_poll(interval, timeout),
+            CompletionStrategy::ServiceRequest => self.wait_complete_srq(timeout),
+        };
+        self.recover_from_timeout(strategy, result)
+    }
+
+    /// Clear the device after a timeout, so that a late response or service request does not disturb the next
+    /// operations. The result of the wait is returned as is.
+    fn recover_from_timeout(
+        &mut self,
+        strategy: CompletionStrategy,
+        result: Result<EventStatus, Error>,
+    ) -> Result<EventStatus, Error> {
+        if matches!(&result, Err(e) if e.status == ErrorType::Tmo) {
+            if strategy == CompletionStrategy::ServiceRequest {
+                self.discard_events(Event::ServiceReq, HandlingMechanism::Queue)
+                    .ok();
+            }
+            self.clear().ok();
+        }
+
+        result
+    }
+
+    fn wait_complete_query(&mut self, timeout: Duration) -> Result<EventStatus, Error> {
+        self.transaction(|tx| {
+            let previous = tx.get_attribute::<TmoValue>()?;
+            tx.set_attribute::<TmoValue>(timeout)?;
+            let result = tx.wait_operation_complete();
+
+            // The error of the wait comes first; a failed restore is only reported otherwise
+            result.and(tx.set_attribute::<TmoValue>(previous))
+        })?;
+        Ok(EventStatus::empty())
+    }
+
+    fn wait_complete_poll(
+        &mut self,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Result<EventStatus, Error> {
+        let previous_ese = self.transaction(|tx| {
+            let previous_ese = tx.event_status_enable()?;
+            tx.set_event_status_enable(previous_ese | EventStatus::OPC)?;
+            Ok(previous_ese)
+        })?;
+
+        let mut other = EventStatus::empty();
+        let result = self.poll_opc(interval, timeout, &mut other);
+        result
+            .and(self.set_event_status_enable(previous_ese))
+            .map(|()| other)
+    }
+
+    fn wait_complete_srq(&mut self, timeout: Duration) -> Result<EventStatus, Error> {
+        let (previous_ese, previous_sre) = self.transaction(|tx| {
+            let previous_ese = tx.event_status_enable()?;
+            let previous_sre = tx.service_request_enable()?;
+            tx.set_event_status_enable(previous_ese | EventStatus::OPC)?;
+            tx.set_service_request_enable(previous_sre | StatusByte::ESB)?;
+            Ok((previous_ese, previous_sre))
+        })?;
+
+        let mut other = EventStatus::empty();
+        let result = self
+            .enable_event(Event::ServiceReq, HandlingMechanism::Queue, 0)
+            .and_then(|()| {
+                let result = self.wait_srq_opc(timeout, &mut other);
+                result.and(self.disable_event(Event::ServiceReq, HandlingMechanism::Queue))
+            });
+
+        let restored = self.transaction(|tx| {
+            let sre = tx.set_service_request_enable(previous_sre);
+            sre.and(tx.set_event_status_enable(previous_ese))
+        });
+        result.and(restored).map(|()| other)
+    }
+
+    /// Send `*OPC`, then serial poll the status byte until the device sets OPC
+    ///
+    /// The session is only locked for each poll, so that other threads can use it in between.
+    fn poll_opc(
+        &mut self,
+        interval: Duration,
+        timeout: Duration,
+        other: &mut EventStatus,
+    ) -> Result<(), Error> {
+        self.start_opc(other)?;
+
+        let started = Instant::now();
+        loop {
+            if self.transaction(|tx| tx.opc_set(other))? {
+                return Ok(());
+            }
+
+            if started.elapsed() >= timeout {
+                return Err(Self::completion_timeout());
+            }
+            std::thread::sleep(interval.min(timeout.saturating_sub(started.elapsed())));
+        }
+    }
+
+    /// Send `*OPC`, then wait for service requests until the device sets OPC
+    ///
+    /// The session is only locked while the status is read after each service request.
+    fn wait_srq_opc(&mut self, timeout: Duration, other: &mut EventStatus) -> Result<(), Error> {
+        self.transaction(|tx| {
+            tx.discard_events(Event::ServiceReq, HandlingMechanism::Queue)?;
+            tx.start_opc(other)
+        })?;
+
+        let started = Instant::now();
+        loop {
+            // Other causes (MAV, other enabled events) may request service before OPC is set
+            let remaining = timeout.saturating_sub(started.elapsed());
+            self.wait_on_event(Event::ServiceReq, remaining)
+                .map_err(|e| match e.status {
+                    ErrorType::Tmo => Self::completion_timeout(),
+                    _ => e,
+                })?;
+
+            if self.transaction(|tx| tx.opc_set(other))? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Clear the standard event register, then send `*OPC`
+    ///
+    /// The bits cleared, other than a stale OPC, are added to `other`.
+    fn start_opc(&mut self, other: &mut EventStatus) -> Result<(), Error> {
+        self.transaction(|tx| {
+            let mut esr = tx.event_status()?;
+            esr.remove(EventStatus::OPC);
+            other.insert(esr);
+            tx.operation_complete()
+        })
+    }
+
+    /// Serial poll the status byte and, if ESB is set, read the standard event register
+    ///
+    /// Returns true if OPC is set. The other bits read are added to `other`.
+    fn opc_set(&mut self, other: &mut EventStatus) -> Result<bool, Error> {
+        if !self.read_status()?.contains(StatusByte::ESB) {
+            return Ok(false);
+        }
+
+        let mut esr = self.event_status()?;
+        let complete = esr.contains(EventStatus::OPC);
+        esr.remove(EventStatus::OPC);
+        other.insert(esr);
+        Ok(complete)
+    }
+
+    fn completion_timeout() -> Error {
+        Error {
+            status: ErrorType::Tmo,
+            description: Some("Pending operations did not complete in time".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+
+    #[test]
+    fn test_identity_parse() {
+        let idn: Identity = "KEYSIGHT TECHNOLOGIES,DSOX1204G,CN12345678,02.12.2021071625\n"
+            .parse()
+            .unwrap();
+        assert_eq!(idn.manufacturer, "KEYSIGHT TECHNOLOGIES");
+        assert_eq!(idn.model, "DSOX1204G");
+        assert_eq!(idn.serial, "CN12345678");
+        assert_eq!(idn.firmware, "02.12.2021071625");
+
+        let short: Identity = "ACME,Model 1".parse().unwrap();
+        assert_eq!(short.model, "Model 1");
+        assert!(short.serial.is_empty());
+
+        assert!("  ".parse::<Identity>().is_err());
+    }
+
+    #[test]
+    fn test_status_flags() {
+        let stb = StatusByte::from_bits(0x50);
+        assert!(stb.contains(StatusByte::RQS));
+        assert!(stb.contains(StatusByte::MAV));
+        assert!(!stb.contains(StatusByte::ESB));
+        assert!(stb.contains(StatusByte::MSS));
+        assert_eq!(format!("{stb:?}"), "StatusByte(MAV | RQS)");
+
+        let ese = EventStatus::OPC | EventStatus::CME | EventStatus::EXE;
+        assert_eq!(ese.bits(), 0x31);
+        assert_eq!(format!("{ese:?}"), "EventStatus(OPC | EXE | CME)");
+    }
+
+    #[test]
+    fn test_timeout_recovery() {
+        // The cleanup fails on a session that is not open, without masking the result of the wait
+        let mut session = Session::null();
+        for strategy in [
+            CompletionStrategy::Query,
+            CompletionStrategy::Poll(Duration::from_millis(10)),
+            CompletionStrategy::ServiceRequest,
+        ] {
+            let error = session
+                .recover_from_timeout(strategy, Err(Session::completion_timeout()))
+                .unwrap_err();
+            assert_eq!(error.status, ErrorType::Tmo);
+
+            let other = session
+                .recover_from_timeout(strategy, Ok(EventStatus::CME))
+                .unwrap();
+            assert_eq!(other, EventStatus::CME);
+        }
+
+        let error = Error::from_msg("Not a timeout");
+        let result = session.recover_from_timeout(CompletionStrategy::Query, Err(error.clone()));
+        assert_eq!(result.unwrap_err().description, error.description);
+    }
+
+    #[test]
+    fn test_wait_complete() {
+        let mut session = get_local_device();
+        let timeout = session.get_attribute::<TmoValue>().unwrap();
+        let ese = session.event_status_enable().unwrap();
+        let sre = session.service_request_enable().unwrap();
+
+        for strategy in [
+            CompletionStrategy::Query,
+            CompletionStrategy::Poll(Duration::from_millis(10)),
+            CompletionStrategy::ServiceRequest,
+        ] {
+            let other = session
+                .wait_complete(strategy, Duration::from_secs(5))
+                .unwrap();
+            assert!(!other.contains(EventStatus::OPC));
+
+            // The session and the enable registers are restored
+            assert_eq!(session.get_attribute::<TmoValue>().unwrap(), timeout);
+            assert_eq!(session.event_status_enable().unwrap(), ese);
+            assert_eq!(session.service_request_enable().unwrap(), sre);
+        }
+    }
+}
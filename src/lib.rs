@@ -7,7 +7,8 @@
 #![warn(missing_debug_implementations)]
 #![warn(clippy::pedantic)]
 
-mod variadic_macro;
+#[macro_use]
+mod flags;
 
 // Internal bindings - raw library access
 #[allow(non_camel_case_types)]
@@ -21,15 +22,67 @@ pub mod bindings;
 pub mod attribute;
 pub mod error;
 pub mod event;
-pub mod security_cookie;
 
 #[macro_use]
+pub mod formatted;
+pub mod gpib;
+pub mod ieee4882;
+pub mod scpi;
+pub mod security_cookie;
+pub mod text;
+
 mod session;
 pub use session::*;
 
 mod resource_manager;
 pub use resource_manager::*;
 
+mod buffered;
+pub use buffered::*;
+
+mod async_io;
+pub use async_io::*;
+
+mod register;
+pub use register::*;
+
+mod register_map;
+pub use register_map::*;
+
+mod window;
+pub use window::*;
+
+mod shared_memory;
+pub use shared_memory::*;
+
+mod event_handler;
+pub use event_handler::*;
+
+mod event_queue;
+pub use event_queue::*;
+
+mod lock;
+pub use lock::*;
+
+mod transaction;
+pub use transaction::*;
+
+mod srq;
+pub use srq::*;
+
+mod usb_interrupt;
+pub use usb_interrupt::*;
+
+#[cfg(any(feature = "tokio", feature = "futures"))]
+mod async_session;
+#[cfg(any(feature = "tokio", feature = "futures"))]
+pub use async_session::*;
+
+#[cfg(feature = "futures")]
+mod event_stream;
+#[cfg(feature = "futures")]
+pub use event_stream::*;
+
 /// Only for testing
 ///
 /// Retrieve a local device session, or panic.  
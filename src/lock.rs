@@ -0,0 +1,324 @@
+//! Scoped VISA resource locks
+//!
+//! `Session::lock_exclusive` and `Session::lock_shared` acquire a VISA lock on the resource, and return a guard
+//! releasing it when dropped, so that an early return cannot leave the instrument locked for other processes:
+//! ```ignore
+//! let mut device = session.lock_exclusive(Duration::from_secs(1))?;
+//! device.write_string("CONF:VOLT:DC")?;
+//! let value: f64 = device.query("READ?")?;
+//! ```
+//!
+//! VISA locks nest: locking a session that already holds a lock of the same type succeeds, and is counted.
+//! Each guard releases one level, and `is_nested` tells whether the lock was already held when it was acquired.
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "Needed for compatibility with the VISA library"
+)]
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{
+    attribute::{
+        rsrc::{RsrcLockState, RsrcName},
+        AccessMode,
+    },
+    bindings,
+    error::{Error, ErrorType},
+    Session,
+};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::{Mutex, MutexGuard, OnceLock},
+    time::Duration,
+};
+
+/// Maximum length of a shared lock key, including the terminator
+const KEY_LENGTH: usize = 256;
+
+/// Shared lock keys held by guards in this process, and the number of guards holding them, by resource name
+fn held_keys() -> MutexGuard<'static, HashMap<String, (String, usize)>> {
+    static KEYS: OnceLock<Mutex<HashMap<String, (String, usize)>>> = OnceLock::new();
+    KEYS.get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Add the key currently held on the resource, if known, to a lock timeout error
+fn describe_held_key(session: &Session, error: Error) -> Error {
+    if !matches!(error.status, ErrorType::Tmo | ErrorType::RsrcLocked) {
+        return error;
+    }
+    let Ok(resource) = session.get_attribute::<RsrcName>() else {
+        return error;
+    };
+    match held_keys().get(&resource) {
+        Some((key, _)) => with_held_key(error, key),
+        None => error,
+    }
+}
+
+fn with_held_key(error: Error, key: &str) -> Error {
+    let description = match error.description {
+        Some(description) => format!("{description} (shared lock held with key \"{key}\")"),
+        None => format!("Shared lock held with key \"{key}\""),
+    };
+    Error {
+        status: error.status,
+        description: Some(description),
+    }
+}
+
+/// An exclusive lock on a resource, released when dropped
+///
+/// The guard dereferences to the locked session.
+#[derive(Debug)]
+#[must_use = "Dropping the guard releases the lock"]
+pub struct ExclusiveLockGuard {
+    session: Session,
+    nested: bool,
+    released: bool,
+}
+impl ExclusiveLockGuard {
+    /// Returns true if the session already held an exclusive lock when this one was acquired
+    #[must_use]
+    pub fn is_nested(&self) -> bool {
+        self.nested
+    }
+
+    /// The current lock state of the resource (`VI_ATTR_RSRC_LOCK_STATE`)
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub fn state(&self) -> Result<AccessMode, Error> {
+        self.session.get_attribute::<RsrcLockState>()
+    }
+
+    /// Release the lock, reporting any error
+    ///
+    /// # Errors
+    /// Will return an error if the session cannot be unlocked
+    pub fn unlock(mut self) -> Result<(), Error> {
+        self.released = true;
+        self.session.unlock()
+    }
+}
+impl Deref for ExclusiveLockGuard {
+    type Target = Session;
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+impl DerefMut for ExclusiveLockGuard {
+    fn deref_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+}
+impl Drop for ExclusiveLockGuard {
+    fn drop(&mut self) {
+        if !self.released {
+            self.session.unlock().ok();
+        }
+    }
+}
+
+/// A shared lock on a resource, released when dropped
+///
+/// Sessions locking the resource with the same key share access to it.
+/// The guard dereferences to the locked session.
+#[derive(Debug)]
+#[must_use = "Dropping the guard releases the lock"]
+pub struct SharedLockGuard {
+    session: Session,
+    key: String,
+    resource: Option<String>,
+    nested: bool,
+    released: bool,
+}
+impl SharedLockGuard {
+    /// The key of the lock, to pass to other sessions sharing it
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns true if the session already held a shared lock when this one was acquired
+    #[must_use]
+    pub fn is_nested(&self) -> bool {
+        self.nested
+    }
+
+    /// The current lock state of the resource (`VI_ATTR_RSRC_LOCK_STATE`)
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub fn state(&self) -> Result<AccessMode, Error> {
+        self.session.get_attribute::<RsrcLockState>()
+    }
+
+    /// Release the lock, reporting any error
+    ///
+    /// # Errors
+    /// Will return an error if the session cannot be unlocked
+    pub fn unlock(mut self) -> Result<(), Error> {
+        self.release()
+    }
+
+    fn release(&mut self) -> Result<(), Error> {
+        if self.released {
+            return Ok(());
+        }
+        self.released = true;
+
+        if let Some(resource) = self.resource.take() {
+            let mut keys = held_keys();
+            if let Some((_, guards)) = keys.get_mut(&resource) {
+                *guards -= 1;
+                if *guards == 0 {
+                    keys.remove(&resource);
+                }
+            }
+        }
+        self.session.unlock()
+    }
+}
+impl Deref for SharedLockGuard {
+    type Target = Session;
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+impl DerefMut for SharedLockGuard {
+    fn deref_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+}
+impl Drop for SharedLockGuard {
+    fn drop(&mut self) {
+        self.release().ok();
+    }
+}
+
+impl Session {
+    /// Acquire an exclusive lock on the resource, released when the guard is dropped
+    ///
+    /// # Errors
+    /// Will return an error if the lock cannot be acquired within `lock_timeout`.
+    /// Timeout errors name the key of the shared lock held on the resource, if it was acquired by this process.
+    pub fn lock_exclusive(&self, lock_timeout: Duration) -> Result<ExclusiveLockGuard, Error> {
+        let lock_timeout = lock_timeout.as_millis() as u32;
+        let status = Error::wrap_binding_status(Some(self.session_id()), || unsafe {
+            bindings::viLock(
+                self.session_id(),
+                bindings::VI_EXCLUSIVE_LOCK,
+                lock_timeout,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        })
+        .map_err(|e| describe_held_key(self, e))?;
+
+        Ok(ExclusiveLockGuard {
+            session: self.clone(),
+            nested: status == bindings::VI_SUCCESS_NESTED_EXCLUSIVE as i32,
+            released: false,
+        })
+    }
+
+    /// Acquire a shared lock on the resource, released when the guard is dropped
+    ///
+    /// If `requested_key` is `None`, VISA generates a key, available from `SharedLockGuard::key`.
+    ///
+    /// # Errors
+    /// Will return an error if the lock cannot be acquired within `lock_timeout`.
+    /// Timeout errors name the key of the shared lock held on the resource, if it was acquired by this process.
+    pub fn lock_shared(
+        &self,
+        lock_timeout: Duration,
+        requested_key: Option<&str>,
+    ) -> Result<SharedLockGuard, Error> {
+        let lock_timeout = lock_timeout.as_millis() as u32;
+        let requested_key = requested_key.map(std::ffi::CString::new).transpose()?;
+        let mut actual_key = vec![std::ffi::c_char::default(); KEY_LENGTH];
+
+        let status = Error::wrap_binding_status(Some(self.session_id()), || unsafe {
+            bindings::viLock(
+                self.session_id(),
+                bindings::VI_SHARED_LOCK,
+                lock_timeout,
+                requested_key
+                    .as_ref()
+                    .map_or(std::ptr::null(), |key| key.as_ptr()),
+                actual_key.as_mut_ptr(),
+            )
+        })
+        .map_err(|e| describe_held_key(self, e))?;
+
+        let mut guard = SharedLockGuard {
+            session: self.clone(),
+            key: String::new(),
+            resource: None,
+            nested: status == bindings::VI_SUCCESS_NESTED_SHARED as i32,
+            released: false,
+        };
+
+        //
+        // Turn the c_char vec back into a string
+        guard.key = unsafe { std::ffi::CStr::from_ptr(actual_key.as_ptr()) }
+            .to_str()
+            .map_err(|_| Error::from_msg("Invalid lock key"))?
+            .to_string();
+
+        if let Ok(resource) = self.get_attribute::<RsrcName>() {
+            held_keys()
+                .entry(resource.clone())
+                .or_insert_with(|| (guard.key.clone(), 0))
+                .1 += 1;
+            guard.resource = Some(resource);
+        }
+        Ok(guard)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+
+    #[test]
+    fn test_lock_guards() {
+        let session = get_local_device();
+        {
+            let outer = session.lock_exclusive(Duration::from_secs(1)).unwrap();
+            assert!(!outer.is_nested());
+            assert_eq!(outer.state().unwrap(), AccessMode::ExclusiveLock);
+
+            let inner = session.lock_exclusive(Duration::from_secs(1)).unwrap();
+            assert!(inner.is_nested());
+            inner.unlock().unwrap();
+            assert_eq!(outer.state().unwrap(), AccessMode::ExclusiveLock);
+        }
+        assert_eq!(
+            session.get_attribute::<RsrcLockState>().unwrap(),
+            AccessMode::NoLock
+        );
+
+        let shared = session
+            .lock_shared(Duration::from_secs(1), Some("libvisa"))
+            .unwrap();
+        assert_eq!(shared.key(), "libvisa");
+        assert_eq!(shared.state().unwrap(), AccessMode::SharedLock);
+    }
+
+    #[test]
+    fn test_with_held_key() {
+        let error = with_held_key(Error::new(bindings::VI_ERROR_TMO, None), "bench");
+        assert_eq!(error.status, ErrorType::Tmo);
+        assert_eq!(
+            error.description.as_deref(),
+            Some("Shared lock held with key \"bench\"")
+        );
+    }
+}
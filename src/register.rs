@@ -0,0 +1,699 @@
+//! Register-based access to VXI, VME and PXI devices
+//!
+//! Registers are addressed by an `AddressSpace` and an offset within it, and accessed with one of the
+//! `RegisterValue` widths (`u8`, `u16`, `u32` or `u64`).
+//!
+//! `Session::peek_reg` and `Session::poke_reg` access a single register:
+//! ```ignore
+//! let vendor: u16 = session.peek_reg(AddressSpace::PxiConfig, 0x00)?;
+//! session.poke_reg(AddressSpace::PxiBar0, 0x40, 0x8000_0000u32)?;
+//! ```
+//!
+//! `Session::move_in`, `Session::move_out` and `Session::move_between` transfer blocks of registers. Offsets must be
+//! aligned to the width of the elements, which is checked before calling VISA.
+//!
+//! `Session::move_in_async` and `Session::move_out_async` start a `viMoveAsyncEx` block transfer between a
+//! register space and a local buffer, and return a `MoveTask` resolving once VISA reports the `IoCompletion`
+//! event. As with `AsyncTask`, the buffer is owned by the task, and dropping the task aborts the transfer:
+//! ```ignore
+//! let transfer = session.move_in_async(AddressSpace::PxiBar1, 0x1000, vec![0u32; 4096])?;
+//! // ... process the previous block while the transfer runs
+//! let block = transfer.await?.data;
+//! ```
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{async_io, bindings, error::Error, Session};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+/// An address space of a register-based device
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    /// Process-local memory, addressed by virtual address
+    Local = bindings::VI_LOCAL_SPACE as u16,
+
+    /// VXI/VME A16 address space
+    A16 = bindings::VI_A16_SPACE as u16,
+
+    /// VXI/VME A24 address space
+    A24 = bindings::VI_A24_SPACE as u16,
+
+    /// VXI/VME A32 address space
+    A32 = bindings::VI_A32_SPACE as u16,
+
+    /// VXI/VME A64 address space
+    A64 = bindings::VI_A64_SPACE as u16,
+
+    /// Memory allocated with `viMemAlloc` on a PXI servant session
+    PxiAlloc = bindings::VI_PXI_ALLOC_SPACE as u16,
+
+    /// PXI/PCI configuration space
+    PxiConfig = bindings::VI_PXI_CFG_SPACE as u16,
+
+    /// PXI/PCI base address register 0
+    PxiBar0 = bindings::VI_PXI_BAR0_SPACE as u16,
+
+    /// PXI/PCI base address register 1
+    PxiBar1 = bindings::VI_PXI_BAR1_SPACE as u16,
+
+    /// PXI/PCI base address register 2
+    PxiBar2 = bindings::VI_PXI_BAR2_SPACE as u16,
+
+    /// PXI/PCI base address register 3
+    PxiBar3 = bindings::VI_PXI_BAR3_SPACE as u16,
+
+    /// PXI/PCI base address register 4
+    PxiBar4 = bindings::VI_PXI_BAR4_SPACE as u16,
+
+    /// PXI/PCI base address register 5
+    PxiBar5 = bindings::VI_PXI_BAR5_SPACE as u16,
+}
+impl TryFrom<u16> for AddressSpace {
+    type Error = Error;
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match u32::from(value) {
+            bindings::VI_LOCAL_SPACE => Ok(Self::Local),
+            bindings::VI_A16_SPACE => Ok(Self::A16),
+            bindings::VI_A24_SPACE => Ok(Self::A24),
+            bindings::VI_A32_SPACE => Ok(Self::A32),
+            bindings::VI_A64_SPACE => Ok(Self::A64),
+            bindings::VI_PXI_ALLOC_SPACE => Ok(Self::PxiAlloc),
+            bindings::VI_PXI_CFG_SPACE => Ok(Self::PxiConfig),
+            bindings::VI_PXI_BAR0_SPACE => Ok(Self::PxiBar0),
+            bindings::VI_PXI_BAR1_SPACE => Ok(Self::PxiBar1),
+            bindings::VI_PXI_BAR2_SPACE => Ok(Self::PxiBar2),
+            bindings::VI_PXI_BAR3_SPACE => Ok(Self::PxiBar3),
+            bindings::VI_PXI_BAR4_SPACE => Ok(Self::PxiBar4),
+            bindings::VI_PXI_BAR5_SPACE => Ok(Self::PxiBar5),
+            _ => Err(Error::new(bindings::VI_ERROR_INV_SPACE, None)),
+        }
+    }
+}
+
+mod sealed {
+    use crate::bindings;
+
+    /// The VISA operations of each register width
+    pub trait Sealed: Sized {
+        /// `viInXX`, or `viInXXEx` for offsets beyond the range of `ViBusAddress`
+        unsafe fn vi_in(
+            vi: bindings::ViSession,
+            space: u16,
+            offset: u64,
+            value: *mut Self,
+        ) -> bindings::ViStatus;
+
+        /// `viOutXX`, or `viOutXXEx` for offsets beyond the range of `ViBusAddress`
+        unsafe fn vi_out(
+            vi: bindings::ViSession,
+            space: u16,
+            offset: u64,
+            value: Self,
+        ) -> bindings::ViStatus;
+
+        /// `viMoveInXX`, or `viMoveInXXEx` for offsets beyond the range of `ViBusAddress`
+        unsafe fn vi_move_in(
+            vi: bindings::ViSession,
+            space: u16,
+            offset: u64,
+            length: bindings::ViBusSize,
+            buffer: *mut Self,
+        ) -> bindings::ViStatus;
+
+        /// `viMoveOutXX`, or `viMoveOutXXEx` for offsets beyond the range of `ViBusAddress`
+        unsafe fn vi_move_out(
+            vi: bindings::ViSession,
+            space: u16,
+            offset: u64,
+            length: bindings::ViBusSize,
+            buffer: *mut Self,
+        ) -> bindings::ViStatus;
+
+        /// `viPeekXX`, reading from an address of a mapped window
+        unsafe fn vi_peek(vi: bindings::ViSession, address: bindings::ViAddr, value: *mut Self);
+
+        /// `viPokeXX`, writing to an address of a mapped window
+        unsafe fn vi_poke(vi: bindings::ViSession, address: bindings::ViAddr, value: Self);
+    }
+}
+
+/// A value that can be transferred to or from a register: `u8`, `u16`, `u32` or `u64`
+pub trait RegisterValue:
+    sealed::Sealed
+    + Copy
+    + Default
+    + Send
+    + Unpin
+    + Into<u64>
+    + TryFrom<u64>
+    + std::fmt::Debug
+    + 'static
+{
+    /// The VISA data width of the value (`VI_WIDTH_8` to `VI_WIDTH_64`)
+    const WIDTH: u16;
+}
+
+macro_rules! impl_register_value {
+    ($(
+        $t:ty => $width:ident,
+        $in:ident, $in_ex:ident, $out:ident, $out_ex:ident,
+        $move_in:ident, $move_in_ex:ident, $move_out:ident, $move_out_ex:ident,
+        $peek:ident, $poke:ident
+    );+) => {
+        $(
+            impl sealed::Sealed for $t {
+                unsafe fn vi_in(
+                    vi: bindings::ViSession,
+                    space: u16,
+                    offset: u64,
+                    value: *mut Self,
+                ) -> bindings::ViStatus {
+                    match bindings::ViBusAddress::try_from(offset) {
+                        Ok(offset) => bindings::$in(vi, space, offset, value),
+                        Err(_) => bindings::$in_ex(vi, space, offset, value),
+                    }
+                }
+
+                unsafe fn vi_out(
+                    vi: bindings::ViSession,
+                    space: u16,
+                    offset: u64,
+                    value: Self,
+                ) -> bindings::ViStatus {
+                    match bindings::ViBusAddress::try_from(offset) {
+                        Ok(offset) => bindings::$out(vi, space, offset, value),
+                        Err(_) => bindings::$out_ex(vi, space, offset, value),
+                    }
+                }
+
+                unsafe fn vi_move_in(
+                    vi: bindings::ViSession,
+                    space: u16,
+                    offset: u64,
+                    length: bindings::ViBusSize,
+                    buffer: *mut Self,
+                ) -> bindings::ViStatus {
+                    match bindings::ViBusAddress::try_from(offset) {
+                        Ok(offset) => bindings::$move_in(vi, space, offset, length, buffer),
+                        Err(_) => bindings::$move_in_ex(vi, space, offset, length, buffer),
+                    }
+                }
+
+                unsafe fn vi_move_out(
+                    vi: bindings::ViSession,
+                    space: u16,
+                    offset: u64,
+                    length: bindings::ViBusSize,
+                    buffer: *mut Self,
+                ) -> bindings::ViStatus {
+                    match bindings::ViBusAddress::try_from(offset) {
+                        Ok(offset) => bindings::$move_out(vi, space, offset, length, buffer),
+                        Err(_) => bindings::$move_out_ex(vi, space, offset, length, buffer),
+                    }
+                }
+
+                unsafe fn vi_peek(
+                    vi: bindings::ViSession,
+                    address: bindings::ViAddr,
+                    value: *mut Self,
+                ) {
+                    bindings::$peek(vi, address, value);
+                }
+
+                unsafe fn vi_poke(vi: bindings::ViSession, address: bindings::ViAddr, value: Self) {
+                    bindings::$poke(vi, address, value);
+                }
+            }
+            impl RegisterValue for $t {
+                const WIDTH: u16 = bindings::$width as u16;
+            }
+        )+
+    };
+}
+impl_register_value!(
+    u8 => VI_WIDTH_8,
+        viIn8, viIn8Ex, viOut8, viOut8Ex,
+        viMoveIn8, viMoveIn8Ex, viMoveOut8, viMoveOut8Ex,
+        viPeek8, viPoke8;
+    u16 => VI_WIDTH_16,
+        viIn16, viIn16Ex, viOut16, viOut16Ex,
+        viMoveIn16, viMoveIn16Ex, viMoveOut16, viMoveOut16Ex,
+        viPeek16, viPoke16;
+    u32 => VI_WIDTH_32,
+        viIn32, viIn32Ex, viOut32, viOut32Ex,
+        viMoveIn32, viMoveIn32Ex, viMoveOut32, viMoveOut32Ex,
+        viPeek32, viPoke32;
+    u64 => VI_WIDTH_64,
+        viIn64, viIn64Ex, viOut64, viOut64Ex,
+        viMoveIn64, viMoveIn64Ex, viMoveOut64, viMoveOut64Ex,
+        viPeek64, viPoke64
+);
+
+/// Check that a register offset is a multiple of the width of `T`
+pub(crate) fn check_alignment<T: RegisterValue>(offset: u64) -> Result<(), Error> {
+    if offset.is_multiple_of(u64::from(T::WIDTH)) {
+        Ok(())
+    } else {
+        Err(Error::new(bindings::VI_ERROR_NSUP_ALIGN_OFFSET, None))
+    }
+}
+
+/// Convert a number of elements to the length of a block transfer
+pub(crate) fn bus_size(length: usize) -> Result<bindings::ViBusSize, Error> {
+    bindings::ViBusSize::try_from(length)
+        .map_err(|_| Error::new(bindings::VI_ERROR_INV_LENGTH, None))
+}
+
+/// The result of an asynchronous block transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveResult<T> {
+    /// The success code of the transfer
+    pub status: bindings::ViStatus,
+
+    /// The number of elements transferred (`VI_ATTR_RET_COUNT`)
+    pub count: usize,
+
+    /// The local buffer of the transfer
+    ///
+    /// For moves in, the data received in its first `count` elements. For moves out, the data that was passed in.
+    pub data: Vec<T>,
+}
+
+/// An asynchronous block transfer that can be awaited or terminated
+///
+/// Dropping the task before it completes aborts the transfer.
+#[derive(Debug)]
+#[must_use = "Dropping the task aborts the transfer"]
+pub struct MoveTask<T: RegisterValue> {
+    session: Session,
+    job_id: bindings::ViJobId,
+    buffer: Option<Vec<T>>,
+    finished: bool,
+}
+impl<T: RegisterValue> MoveTask<T> {
+    /// The VISA job id of the transfer
+    #[must_use]
+    pub fn job_id(&self) -> bindings::ViJobId {
+        self.job_id
+    }
+
+    /// Abort the transfer before it completes
+    ///
+    /// # Errors
+    /// Will return an error if the transfer cannot be terminated
+    pub fn terminate(mut self) -> Result<(), Error> {
+        self.cancel()
+    }
+
+    /// Block the current thread until the transfer completes
+    ///
+    /// # Errors
+    /// Will return an error if the transfer fails
+    pub fn wait(mut self) -> Result<MoveResult<T>, Error> {
+        async_io::block_on(&mut self)
+    }
+
+    fn cancel(&mut self) -> Result<(), Error> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        async_io::terminate_job(
+            self.session.session_id(),
+            self.job_id,
+            Box::new(self.buffer.take().unwrap_or_default()),
+        )
+    }
+}
+impl<T: RegisterValue> Future for MoveTask<T> {
+    type Output = Result<MoveResult<T>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(Error::from_msg("Move task polled after completion")));
+        }
+
+        let vi = this.session.session_id();
+        let (status, count) = ready!(async_io::poll_job(vi, this.job_id, cx));
+        this.finished = true;
+        if status < 0 {
+            return Poll::Ready(Err(Error::new(status, Some(vi))));
+        }
+
+        Poll::Ready(Ok(MoveResult {
+            status,
+            count,
+            data: this.buffer.take().unwrap_or_default(),
+        }))
+    }
+}
+impl<T: RegisterValue> Drop for MoveTask<T> {
+    fn drop(&mut self) {
+        self.cancel().ok();
+    }
+}
+
+impl Session {
+    /// Read a single register with `viInXX`
+    ///
+    /// The value is read with the address modifier of the `SrcAccessPriv` attribute, and converted from the byte
+    /// order of the `SrcByteOrder` attribute. Offsets beyond 32 bits use `viInXXEx`.
+    ///
+    /// # Errors
+    /// Will return an error if the register cannot be read
+    pub fn peek_reg<T: RegisterValue>(&self, space: AddressSpace, offset: u64) -> Result<T, Error> {
+        let vi = self.session_id();
+        let mut value = T::default();
+        Error::wrap_binding(Some(vi), || unsafe {
+            T::vi_in(vi, space as u16, offset, &raw mut value)
+        })?;
+        Ok(value)
+    }
+
+    /// Write a single register with `viOutXX`
+    ///
+    /// The value is written with the address modifier of the `DestAccessPriv` attribute, and converted to the byte
+    /// order of the `DestByteOrder` attribute. Offsets beyond 32 bits use `viOutXXEx`.
+    ///
+    /// # Errors
+    /// Will return an error if the register cannot be written
+    pub fn poke_reg<T: RegisterValue>(
+        &self,
+        space: AddressSpace,
+        offset: u64,
+        value: T,
+    ) -> Result<(), Error> {
+        let vi = self.session_id();
+        Error::wrap_binding(Some(vi), || unsafe {
+            T::vi_out(vi, space as u16, offset, value)
+        })
+    }
+
+    /// Read `buffer.len()` consecutive elements from a register space with `viMoveInXX`
+    ///
+    /// The source offset is incremented according to the `SrcIncrement` attribute; set it to 0 to read a FIFO register.
+    /// The byte order and address modifier are set by the `SrcByteOrder` and `SrcAccessPriv` attributes.
+    ///
+    /// # Errors
+    /// Will return an error if the offset is not aligned to the width of `T`, or the transfer fails
+    pub fn move_in<T: RegisterValue>(
+        &self,
+        space: AddressSpace,
+        offset: u64,
+        buffer: &mut [T],
+    ) -> Result<(), Error> {
+        check_alignment::<T>(offset)?;
+        let length = bus_size(buffer.len())?;
+        if length == 0 {
+            return Ok(());
+        }
+
+        let vi = self.session_id();
+        Error::wrap_binding(Some(vi), || unsafe {
+            T::vi_move_in(vi, space as u16, offset, length, buffer.as_mut_ptr())
+        })
+    }
+
+    /// Write `data` to consecutive elements of a register space with `viMoveOutXX`
+    ///
+    /// The destination offset is incremented according to the `DestIncrement` attribute; set it to 0 to write a
+    /// FIFO register. The byte order and address modifier are set by the `DestByteOrder` and `DestAccessPriv`
+    /// attributes.
+    ///
+    /// # Errors
+    /// Will return an error if the offset is not aligned to the width of `T`, or the transfer fails
+    pub fn move_out<T: RegisterValue>(
+        &self,
+        space: AddressSpace,
+        offset: u64,
+        data: &[T],
+    ) -> Result<(), Error> {
+        check_alignment::<T>(offset)?;
+        let length = bus_size(data.len())?;
+        if length == 0 {
+            return Ok(());
+        }
+
+        // VISA does not write to the buffer of a move out, despite the signature
+        let vi = self.session_id();
+        Error::wrap_binding(Some(vi), || unsafe {
+            T::vi_move_out(vi, space as u16, offset, length, data.as_ptr().cast_mut())
+        })
+    }
+
+    /// Copy `length` elements of type `S` between two register spaces with `viMove`
+    ///
+    /// The source is read as `S` and the destination written as `D`, so the size of the block must be a multiple of
+    /// both widths. The offsets are incremented according to the `SrcIncrement` and `DestIncrement` attributes.
+    /// Offsets beyond 32 bits use `viMoveEx`.
+    ///
+    /// # Errors
+    /// Will return an error if an offset is not aligned to its width, the block does not divide into elements of
+    /// type `D`, or the transfer fails
+    pub fn move_between<S: RegisterValue, D: RegisterValue>(
+        &self,
+        (src_space, src_offset): (AddressSpace, u64),
+        (dest_space, dest_offset): (AddressSpace, u64),
+        length: usize,
+    ) -> Result<(), Error> {
+        check_alignment::<S>(src_offset)?;
+        check_alignment::<D>(dest_offset)?;
+        let bytes = length.checked_mul(usize::from(S::WIDTH));
+        if bytes.is_none_or(|bytes| !bytes.is_multiple_of(usize::from(D::WIDTH))) {
+            return Err(Error::new(bindings::VI_ERROR_INV_LENGTH, None));
+        }
+        let length = bus_size(length)?;
+        if length == 0 {
+            return Ok(());
+        }
+
+        let vi = self.session_id();
+        let offsets = (
+            bindings::ViBusAddress::try_from(src_offset),
+            bindings::ViBusAddress::try_from(dest_offset),
+        );
+        Error::wrap_binding(Some(vi), || unsafe {
+            match offsets {
+                (Ok(src), Ok(dest)) => bindings::viMove(
+                    vi,
+                    src_space as u16,
+                    src,
+                    S::WIDTH,
+                    dest_space as u16,
+                    dest,
+                    D::WIDTH,
+                    length,
+                ),
+                _ => bindings::viMoveEx(
+                    vi,
+                    src_space as u16,
+                    src_offset,
+                    S::WIDTH,
+                    dest_space as u16,
+                    dest_offset,
+                    D::WIDTH,
+                    length,
+                ),
+            }
+        })
+    }
+
+    /// Start a `viMoveAsyncEx` transfer between a register space and the local buffer `buffer`
+    fn start_move<T: RegisterValue>(
+        &self,
+        mut buffer: Vec<T>,
+        space: AddressSpace,
+        offset: u64,
+        move_in: bool,
+    ) -> Result<MoveTask<T>, Error> {
+        check_alignment::<T>(offset)?;
+        let length = bus_size(buffer.len())?;
+        self.enable_async_io()?;
+
+        let vi = self.session_id();
+        let local = (
+            AddressSpace::Local as u16,
+            buffer.as_mut_ptr() as bindings::ViBusAddress64,
+        );
+        let (src, dest) = if move_in {
+            ((space as u16, offset), local)
+        } else {
+            (local, (space as u16, offset))
+        };
+
+        let mut job_id = 0;
+        Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viMoveAsyncEx(
+                vi,
+                src.0,
+                src.1,
+                T::WIDTH,
+                dest.0,
+                dest.1,
+                T::WIDTH,
+                length,
+                &raw mut job_id,
+            )
+        })?;
+
+        Ok(MoveTask {
+            session: self.clone(),
+            job_id,
+            buffer: Some(buffer),
+            finished: false,
+        })
+    }
+
+    /// Start an asynchronous transfer of `buffer.len()` elements from a register space into `buffer`
+    ///
+    /// The source offset is incremented according to the `SrcIncrement` attribute; set it to 0 to read a FIFO register.
+    ///
+    /// # Errors
+    /// Will return an error if the transfer cannot be started
+    pub fn move_in_async<T: RegisterValue>(
+        &self,
+        space: AddressSpace,
+        offset: u64,
+        buffer: Vec<T>,
+    ) -> Result<MoveTask<T>, Error> {
+        self.start_move(buffer, space, offset, true)
+    }
+
+    /// Start an asynchronous transfer of `data` to a register space
+    ///
+    /// The destination offset is incremented according to the `DestIncrement` attribute; set it to 0 to write a
+    /// FIFO register.
+    ///
+    /// # Errors
+    /// Will return an error if the transfer cannot be started
+    pub fn move_out_async<T: RegisterValue>(
+        &self,
+        space: AddressSpace,
+        offset: u64,
+        data: Vec<T>,
+    ) -> Result<MoveTask<T>, Error> {
+        self.start_move(data, space, offset, false)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::task::Waker;
+
+    #[test]
+    fn test_address_space() {
+        for space in [
+            AddressSpace::Local,
+            AddressSpace::A24,
+            AddressSpace::PxiConfig,
+            AddressSpace::PxiBar5,
+        ] {
+            assert_eq!(AddressSpace::try_from(space as u16).unwrap(), space);
+        }
+        assert!(AddressSpace::try_from(0xFFFF).is_err());
+        assert_eq!(<u32 as RegisterValue>::WIDTH, 4);
+    }
+
+    #[test]
+    fn test_check_alignment() {
+        assert!(check_alignment::<u8>(0x1003).is_ok());
+        assert!(check_alignment::<u32>(0x1004).is_ok());
+
+        let error = check_alignment::<u32>(0x1002).unwrap_err();
+        assert_eq!(error.status, crate::error::ErrorType::NsupAlignOffset);
+        assert!(check_alignment::<u64>(0x1004).is_err());
+    }
+
+    #[test]
+    fn test_move_between_checks() {
+        // Rejected before reaching VISA
+        let session = Session::null();
+        let src = (AddressSpace::A24, 0x100);
+        let dest = (AddressSpace::A24, 0x200);
+        assert!(session.move_between::<u16, u32>(src, dest, 3).is_err());
+        assert!(session
+            .move_between::<u16, u16>(src, (AddressSpace::A24, 0x201), 2)
+            .is_err());
+        assert!(session.move_between::<u16, u32>(src, dest, 0).is_ok());
+    }
+
+    /// Open the register-based device named by `LOCAL_REGISTER_DEVICE`, such as `PXI0::2-0::INSTR`
+    pub(crate) fn get_register_device() -> Session {
+        let rm = crate::ResourceManager::new().unwrap();
+        let resource = std::env::var("LOCAL_REGISTER_DEVICE")
+            .expect("LOCAL_REGISTER_DEVICE must name a register-based device");
+        Session::new(&rm, &resource, crate::SessionOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_peek_poke() {
+        let session = get_register_device();
+        let vendor: u16 = session.peek_reg(AddressSpace::PxiConfig, 0x00).unwrap();
+        assert_ne!(vendor, 0xFFFF);
+
+        // The command register is writable
+        let command: u16 = session.peek_reg(AddressSpace::PxiConfig, 0x04).unwrap();
+        session
+            .poke_reg(AddressSpace::PxiConfig, 0x04, command)
+            .unwrap();
+        assert_eq!(
+            session
+                .peek_reg::<u16>(AddressSpace::PxiConfig, 0x04)
+                .unwrap(),
+            command
+        );
+    }
+
+    #[test]
+    fn test_block_moves() {
+        let session = get_register_device();
+        let mut header = [0u32; 4];
+        session
+            .move_in(AddressSpace::PxiConfig, 0x00, &mut header)
+            .unwrap();
+        assert_eq!(
+            header[0] & 0xFFFF,
+            u32::from(
+                session
+                    .peek_reg::<u16>(AddressSpace::PxiConfig, 0x00)
+                    .unwrap()
+            )
+        );
+
+        let mut bytes = [0u8; 16];
+        session
+            .move_in(AddressSpace::PxiConfig, 0x00, &mut bytes)
+            .unwrap();
+        assert_eq!(bytes[..4], header[0].to_le_bytes());
+    }
+
+    #[test]
+    fn test_move_task() {
+        let mut task = MoveTask {
+            session: Session::null(),
+            job_id: 7,
+            buffer: Some(vec![0u16; 8]),
+            finished: false,
+        };
+
+        let mut context = Context::from_waker(Waker::noop());
+        assert!(Pin::new(&mut task).poll(&mut context).is_pending());
+
+        async_io::complete_job(bindings::VI_NULL, 7, 0, 8);
+        let Poll::Ready(result) = Pin::new(&mut task).poll(&mut context) else {
+            panic!("Task not completed");
+        };
+        let result = result.unwrap();
+        assert_eq!(result.count, 8);
+        assert_eq!(result.data.len(), 8);
+    }
+}
@@ -0,0 +1,497 @@
+//! Typed register maps for register-based devices
+//!
+//! The `register_map!` macro describes the registers of a VXI, VME or PXI module: their names, address spaces,
+//! offsets, widths and access modes, and the bit fields they hold. It generates a type per register, holding its
+//! raw value, and a map type giving access to each register of a device through its session:
+//! ```ignore
+//! libvisa::register_map! {
+//!     /// Registers of a VXI device
+//!     pub struct VxiDevice {
+//!         /// ID register
+//!         id: Id @ A16 + 0x00 as u16, read_only {
+//!             /// Manufacturer ID
+//!             MANUFACTURER: 0..12 as u16,
+//!             /// Device class
+//!             CLASS: 14..16 as DeviceClass {
+//!                 /// Memory device
+//!                 Memory = 0,
+//!                 /// Extended device
+//!                 Extended = 1,
+//!                 /// Message-based device
+//!                 MessageBased = 2,
+//!                 /// Register-based device
+//!                 RegisterBased = 3,
+//!             },
+//!         }
+//!         /// Status/control register
+//!         status: Status @ A16 + 0x04 as u16, read_write {
+//!             /// Soft reset
+//!             RESET: 0..1 as bool,
+//!             /// Set once the device passed its self test
+//!             PASSED: 2..3 as bool,
+//!         }
+//!     }
+//! }
+//!
+//! let device = VxiDevice::with_base(session, 0xC000);
+//! let class = device.id().read()?.get(Id::CLASS);
+//! device.status().modify(|status| status.with(Status::RESET, true))?;
+//! ```
+//!
+//! Each register is accessed with `Session::peek_reg` and `Session::poke_reg`, at its offset from the base of the
+//! map. Fields are bit ranges `low..high`, read as `bool`, an unsigned integer, or an enum declared along with the
+//! field. Access modes are `read_only`, `write_only` or `read_write`.
+
+use crate::{error::Error, AddressSpace, RegisterValue, Session};
+use std::marker::PhantomData;
+
+/// A register described by `register_map!`, wrapping its raw value
+pub trait Register: Copy + Default {
+    /// The width of the register
+    type Raw: RegisterValue;
+
+    /// The address space of the register
+    const SPACE: AddressSpace;
+
+    /// The offset of the register from the base of its map
+    const OFFSET: u64;
+
+    /// Build the register from its raw value
+    fn from_raw(raw: Self::Raw) -> Self;
+
+    /// Get the raw value of the register
+    fn raw(self) -> Self::Raw;
+}
+
+/// A register that can be read
+pub trait Readable: Register {}
+
+/// A register that can be written
+pub trait Writable: Register {}
+
+/// A value that can be held by a bit field: `bool`, an unsigned integer, or an enum declared by `register_map!`
+pub trait FieldValue: Sized {
+    /// Convert the bits of a field, shifted down to bit 0, to a value
+    ///
+    /// Returns `None` if the bits do not correspond to a value of the type.
+    fn from_field(bits: u64) -> Option<Self>;
+
+    /// Convert the value to the bits of a field, shifted down to bit 0
+    fn into_field(self) -> u64;
+}
+impl FieldValue for bool {
+    fn from_field(bits: u64) -> Option<Self> {
+        Some(bits != 0)
+    }
+
+    fn into_field(self) -> u64 {
+        u64::from(self)
+    }
+}
+macro_rules! impl_field_value {
+    ($($t:ty),+) => {
+        $(
+            impl FieldValue for $t {
+                fn from_field(bits: u64) -> Option<Self> {
+                    Self::try_from(bits).ok()
+                }
+
+                fn into_field(self) -> u64 {
+                    u64::from(self)
+                }
+            }
+        )+
+    };
+}
+impl_field_value!(u8, u16, u32, u64);
+
+/// The bits `low..high` of a register of type `R`, holding a value of type `V`
+pub struct Field<R, V> {
+    low: u16,
+    high: u16,
+    _types: PhantomData<fn() -> (R, V)>,
+}
+impl<R: Register, V: FieldValue> Field<R, V> {
+    /// Describe the bits `low..high` of the register
+    ///
+    /// # Panics
+    /// Panics if the range is empty, or does not fit in the register. In the constants generated by `register_map!`,
+    /// this is a compile-time error.
+    #[must_use]
+    pub const fn new(low: u16, high: u16) -> Self {
+        assert!(
+            low < high && high <= R::Raw::WIDTH * 8,
+            "Field outside of its register"
+        );
+        Self {
+            low,
+            high,
+            _types: PhantomData,
+        }
+    }
+
+    /// The lowest bit of the field
+    #[must_use]
+    pub const fn low(&self) -> u16 {
+        self.low
+    }
+
+    /// The number of bits of the field
+    #[must_use]
+    pub const fn width(&self) -> u16 {
+        self.high - self.low
+    }
+
+    /// The bits of the field, in place in the register
+    #[must_use]
+    pub const fn mask(&self) -> u64 {
+        (u64::MAX >> (64 - self.width())) << self.low
+    }
+
+    /// Read the field from a register value
+    ///
+    /// Returns `None` if the field holds a value not declared by its enum.
+    pub fn get(&self, register: R) -> Option<V> {
+        let raw: u64 = register.raw().into();
+        V::from_field((raw & self.mask()) >> self.low)
+    }
+
+    /// Write the field of a register value, leaving the other fields unchanged
+    ///
+    /// The bits of `value` that do not fit in the field are ignored.
+    pub fn set(&self, register: R, value: V) -> R {
+        let raw: u64 = register.raw().into();
+        let raw = (raw & !self.mask()) | ((value.into_field() << self.low) & self.mask());
+
+        // The field is within the width of the register
+        R::from_raw(R::Raw::try_from(raw).unwrap_or_default())
+    }
+}
+impl<R, V> Clone for Field<R, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<R, V> Copy for Field<R, V> {}
+impl<R, V> std::fmt::Debug for Field<R, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Field({}..{})", self.low, self.high)
+    }
+}
+
+/// A register of type `R` of a device, at a given offset in its address space
+#[derive(Debug, Clone, Copy)]
+pub struct RegisterRef<'a, R> {
+    session: &'a Session,
+    offset: u64,
+    _register: PhantomData<R>,
+}
+impl<'a, R: Register> RegisterRef<'a, R> {
+    /// The register of a map starting at `base`, accessed through `session`
+    #[must_use]
+    pub fn new(session: &'a Session, base: u64) -> Self {
+        Self {
+            session,
+            offset: base + R::OFFSET,
+            _register: PhantomData,
+        }
+    }
+
+    /// The address space of the register
+    #[must_use]
+    pub fn space(&self) -> AddressSpace {
+        R::SPACE
+    }
+
+    /// The offset of the register in its address space
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Read the register
+    ///
+    /// # Errors
+    /// Will return an error if the register cannot be read
+    pub fn read(&self) -> Result<R, Error>
+    where
+        R: Readable,
+    {
+        self.session
+            .peek_reg(R::SPACE, self.offset)
+            .map(R::from_raw)
+    }
+
+    /// Write the register
+    ///
+    /// # Errors
+    /// Will return an error if the register cannot be written
+    pub fn write(&self, value: R) -> Result<(), Error>
+    where
+        R: Writable,
+    {
+        self.session.poke_reg(R::SPACE, self.offset, value.raw())
+    }
+
+    /// Write the register with the fields set by `f`, and the other bits cleared
+    ///
+    /// # Errors
+    /// Will return an error if the register cannot be written
+    pub fn write_with(&self, f: impl FnOnce(R) -> R) -> Result<(), Error>
+    where
+        R: Writable,
+    {
+        self.write(f(R::default()))
+    }
+
+    /// Read the register, change it with `f`, and write it back
+    ///
+    /// Other threads cannot use the session between the read and the write; other sessions and processes can.
+    ///
+    /// # Errors
+    /// Will return an error if the register cannot be read or written
+    pub fn modify(&self, f: impl FnOnce(R) -> R) -> Result<(), Error>
+    where
+        R: Readable + Writable,
+    {
+        self.session.with_lock(|_| {
+            let value = self.read()?;
+            self.write(f(value))
+        })
+    }
+}
+
+/// Describes the register map of a register-based device
+///
+/// See the `register_map` module for the syntax and the generated types.
+#[macro_export]
+macro_rules! register_map {
+    (@access read_only $register:ident) => {
+        impl $crate::Readable for $register {}
+    };
+    (@access write_only $register:ident) => {
+        impl $crate::Writable for $register {}
+    };
+    (@access read_write $register:ident) => {
+        impl $crate::Readable for $register {}
+        impl $crate::Writable for $register {}
+    };
+
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $map:ident {
+            $(
+                $(#[$register_meta:meta])*
+                $accessor:ident: $register:ident @ $space:ident + $offset:literal as $raw:ident, $access:ident {
+                    $(
+                        $(#[$field_meta:meta])*
+                        $field:ident: $low:literal..$high:literal as $value:ident $({
+                            $(
+                                $(#[$variant_meta:meta])*
+                                $variant:ident = $bits:literal
+                            ),* $(,)?
+                        })?
+                    ),* $(,)?
+                }
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        $vis struct $map {
+            session: $crate::Session,
+            base: u64,
+        }
+        // Not every map uses all the generated methods
+        #[allow(dead_code)]
+        impl $map {
+            /// Access the registers of a device at their offsets in its address spaces
+            #[must_use]
+            $vis fn new(session: $crate::Session) -> Self {
+                Self::with_base(session, 0)
+            }
+
+            /// Access the registers of a device at their offsets from `base`
+            #[must_use]
+            $vis fn with_base(session: $crate::Session, base: u64) -> Self {
+                Self { session, base }
+            }
+
+            /// The session of the device
+            #[must_use]
+            $vis fn session(&self) -> &$crate::Session {
+                &self.session
+            }
+
+            $(
+                $(#[$register_meta])*
+                #[must_use]
+                $vis fn $accessor(&self) -> $crate::RegisterRef<'_, $register> {
+                    $crate::RegisterRef::new(&self.session, self.base)
+                }
+            )*
+        }
+
+        $(
+            $(#[$register_meta])*
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+            $vis struct $register(pub $raw);
+            #[allow(dead_code)]
+            impl $register {
+                $(
+                    $(#[$field_meta])*
+                    $vis const $field: $crate::Field<Self, $value> = $crate::Field::new($low, $high);
+                )*
+
+                /// Read a field of the register
+                ///
+                /// Returns `None` if the field holds a value not declared by its enum.
+                #[must_use]
+                $vis fn get<V: $crate::FieldValue>(self, field: $crate::Field<Self, V>) -> Option<V> {
+                    field.get(self)
+                }
+
+                /// Return the register with a field changed
+                #[must_use]
+                $vis fn with<V: $crate::FieldValue>(self, field: $crate::Field<Self, V>, value: V) -> Self {
+                    field.set(self, value)
+                }
+
+                /// Change a field of the register
+                $vis fn set<V: $crate::FieldValue>(&mut self, field: $crate::Field<Self, V>, value: V) {
+                    *self = field.set(*self, value);
+                }
+            }
+            impl $crate::Register for $register {
+                type Raw = $raw;
+                const SPACE: $crate::AddressSpace = $crate::AddressSpace::$space;
+                const OFFSET: u64 = $offset;
+
+                fn from_raw(raw: $raw) -> Self {
+                    Self(raw)
+                }
+
+                fn raw(self) -> $raw {
+                    self.0
+                }
+            }
+            $crate::register_map!(@access $access $register);
+
+            // Evaluate the field constants, so that invalid ranges fail to compile even if a field is never used
+            const _: () = {
+                $(let _ = $register::$field;)*
+            };
+
+            $($(
+                #[doc = concat!("Values of the `", stringify!($field), "` field of `", stringify!($register), "`")]
+                #[repr(u64)]
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                $vis enum $value {
+                    $(
+                        $(#[$variant_meta])*
+                        $variant = $bits,
+                    )*
+                }
+                impl $crate::FieldValue for $value {
+                    fn from_field(bits: u64) -> Option<Self> {
+                        match bits {
+                            $($bits => Some(Self::$variant),)*
+                            _ => None,
+                        }
+                    }
+
+                    fn into_field(self) -> u64 {
+                        self as u64
+                    }
+                }
+            )?)*
+        )*
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::register::test::get_register_device;
+
+    register_map! {
+        /// The standard header of the PCI configuration space
+        pub struct PciConfig {
+            /// Vendor and device ID
+            ids: Ids @ PxiConfig + 0x00 as u32, read_only {
+                /// Vendor ID
+                VENDOR: 0..16 as u16,
+                /// Device ID
+                DEVICE: 16..32 as u16,
+            }
+            /// Command register
+            command: Command @ PxiConfig + 0x04 as u16, read_write {
+                /// Respond to memory space accesses
+                MEMORY: 1..2 as bool,
+                /// Act as a bus master
+                BUS_MASTER: 2..3 as bool,
+            }
+            /// Class code and revision
+            class: Class @ PxiConfig + 0x08 as u32, read_only {
+                /// Revision ID
+                REVISION: 0..8 as u8,
+                /// Base class
+                BASE: 24..32 as BaseClass {
+                    /// Mass storage controller
+                    Storage = 0x01,
+                    /// Network controller
+                    Network = 0x02,
+                    /// Data acquisition and signal processing controller
+                    Acquisition = 0x11,
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn test_fields() {
+        let ids = Ids(0x1234_1093);
+        assert_eq!(ids.get(Ids::VENDOR), Some(0x1093));
+        assert_eq!(ids.get(Ids::DEVICE), Some(0x1234));
+
+        let mut command = Command::default().with(Command::BUS_MASTER, true);
+        assert_eq!(command, Command(0b100));
+        command.set(Command::MEMORY, true);
+        command.set(Command::BUS_MASTER, false);
+        assert_eq!(command, Command(0b010));
+        assert_eq!(command.get(Command::MEMORY), Some(true));
+
+        let class = Class(0x1100_0003);
+        assert_eq!(class.get(Class::BASE), Some(BaseClass::Acquisition));
+        assert_eq!(class.get(Class::REVISION), Some(3));
+        assert_eq!(Class(0xFF00_0000).get(Class::BASE), None);
+        assert_eq!(
+            Class::default().with(Class::BASE, BaseClass::Network),
+            Class(0x0200_0000)
+        );
+
+        // Values wider than the field are truncated
+        let field = Field::<Command, u16>::new(4, 8);
+        assert_eq!(field.mask(), 0xF0);
+        assert_eq!(field.set(Command(0xFFFF), 0x12), Command(0xFF2F));
+    }
+
+    #[test]
+    fn test_register_ref() {
+        let map = PciConfig::with_base(Session::null(), 0x100);
+        assert_eq!(map.ids().offset(), 0x100);
+        assert_eq!(map.command().offset(), 0x104);
+        assert_eq!(map.class().space(), AddressSpace::PxiConfig);
+    }
+
+    #[test]
+    fn test_register_map() {
+        let map = PciConfig::new(get_register_device());
+        let ids = map.ids().read().unwrap();
+        assert_ne!(ids.get(Ids::VENDOR), Some(0xFFFF));
+
+        let command = map.command().read().unwrap();
+        map.command().modify(|command| command).unwrap();
+        assert_eq!(map.command().read().unwrap(), command);
+    }
+}
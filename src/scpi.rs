@@ -0,0 +1,377 @@
+//! SCPI status subsystem
+//!
+//! SCPI instruments report their state through the `STATus:OPERation` and `STATus:QUEStionable`
+//! register sets, each made of a condition, event, enable and transition filter registers.
+//! Enabled events are summarized in the status byte (OSB and QSB), and can be routed to a service request.
+//!
+//! # Example
+//! ```ignore
+//! use libvisa::scpi::{Questionable, QuestionableStatus};
+//!
+//! // Request service whenever the supply reports an overvoltage
+//! session.route_status_to_srq::<Questionable>(QuestionableStatus::VOLTAGE)?;
+//!
+//! let report = session.wait_service_request(Duration::from_secs(10))?;
+//! if report.questionable.is_some_and(|q| q.contains(QuestionableStatus::VOLTAGE)) {
+//!     println!("Overvoltage!");
+//! }
+//! ```
+use crate::{
+    bindings,
+    error::Error,
+    event,
+    ieee4882::{EventStatus, StatusByte},
+    Session,
+};
+
+impl_flags!(
+    "The SCPI `STATus:OPERation` register"
+    "Bits 8 to 12 are instrument defined."
+    OperationStatus(u16) {
+        "The instrument is calibrating"
+        CALIBRATING = 0x0001;
+
+        "The instrument is waiting for signals to settle"
+        SETTLING = 0x0002;
+
+        "The instrument is changing range"
+        RANGING = 0x0004;
+
+        "A sweep is in progress"
+        SWEEPING = 0x0008;
+
+        "A measurement is in progress"
+        MEASURING = 0x0010;
+
+        "The instrument is waiting for a trigger"
+        WAITING_FOR_TRIGGER = 0x0020;
+
+        "The instrument is waiting for an arm"
+        WAITING_FOR_ARM = 0x0040;
+
+        "The instrument is correcting data"
+        CORRECTING = 0x0080;
+
+        "Summary of the `STATus:OPERation:INSTrument` registers"
+        INSTRUMENT_SUMMARY = 0x2000;
+
+        "A user defined program is running"
+        PROGRAM_RUNNING = 0x4000;
+    }
+);
+
+impl_flags!(
+    "The SCPI `STATus:QUEStionable` register"
+    "Bits 9 to 12 are instrument defined, and are often used by power supplies for protection events."
+    QuestionableStatus(u16) {
+        "A voltage is questionable (overvoltage, or a measurement out of range)"
+        VOLTAGE = 0x0001;
+
+        "A current is questionable (overcurrent, or a measurement out of range)"
+        CURRENT = 0x0002;
+
+        "A time value is questionable"
+        TIME = 0x0004;
+
+        "A power value is questionable"
+        POWER = 0x0008;
+
+        "A temperature is questionable (overtemperature)"
+        TEMPERATURE = 0x0010;
+
+        "A frequency is questionable"
+        FREQUENCY = 0x0020;
+
+        "A phase value is questionable"
+        PHASE = 0x0040;
+
+        "A modulation value is questionable"
+        MODULATION = 0x0080;
+
+        "A calibration is questionable"
+        CALIBRATION = 0x0100;
+
+        "Summary of the `STATus:QUEStionable:INSTrument` registers"
+        INSTRUMENT_SUMMARY = 0x2000;
+
+        "A command was executed with a warning"
+        COMMAND_WARNING = 0x4000;
+    }
+);
+
+/// A SCPI status register set
+pub trait StatusRegister {
+    /// The flags type of the register
+    type Flags: Copy + From<u16> + Into<u16>;
+
+    /// The SCPI node of the register set
+    const NODE: &'static str;
+
+    /// The status byte bit summarizing the register set
+    const SUMMARY: StatusByte;
+}
+
+/// The `STATus:OPERation` register set
+#[derive(Debug, Clone, Copy)]
+pub struct Operation;
+impl StatusRegister for Operation {
+    type Flags = OperationStatus;
+    const NODE: &'static str = "STAT:OPER";
+    const SUMMARY: StatusByte = StatusByte::OSB;
+}
+
+/// The `STATus:QUEStionable` register set
+#[derive(Debug, Clone, Copy)]
+pub struct Questionable;
+impl StatusRegister for Questionable {
+    type Flags = QuestionableStatus;
+    const NODE: &'static str = "STAT:QUES";
+    const SUMMARY: StatusByte = StatusByte::QSB;
+}
+
+/// The registers that caused a service request, returned by `Session::wait_service_request`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatusReport {
+    /// The status byte read by the serial poll
+    pub status_byte: StatusByte,
+
+    /// The standard event status register, if ESB was set
+    pub standard: Option<EventStatus>,
+
+    /// The `STATus:OPERation` event register, if OSB was set
+    pub operation: Option<OperationStatus>,
+
+    /// The `STATus:QUEStionable` event register, if QSB was set
+    pub questionable: Option<QuestionableStatus>,
+}
+
+impl StatusReport {
+    /// Decode a status byte, reading the event registers summarized by its set bits with `query`
+    fn decode(
+        status_byte: StatusByte,
+        mut query: impl FnMut(&str) -> Result<u16, Error>,
+    ) -> Result<Self, Error> {
+        let mut report = StatusReport {
+            status_byte,
+            ..StatusReport::default()
+        };
+
+        if status_byte.contains(StatusByte::ESB) {
+            let esr = u8::try_from(query("*ESR?")?)
+                .map_err(|_| Error::from_msg("*ESR?: value out of range"))?;
+            report.standard = Some(EventStatus::from_bits(esr));
+        }
+        if status_byte.contains(Operation::SUMMARY) {
+            report.operation = Some(query(&format!("{}:EVEN?", Operation::NODE))?.into());
+        }
+        if status_byte.contains(Questionable::SUMMARY) {
+            report.questionable = Some(query(&format!("{}:EVEN?", Questionable::NODE))?.into());
+        }
+
+        Ok(report)
+    }
+}
+
+impl Session {
+    /// Read the condition register of a status register set (`STATus:<node>:CONDition?`)
+    ///
+    /// The condition register reflects the current state of the instrument, and is not cleared by reading
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn status_condition<R: StatusRegister>(&mut self) -> Result<R::Flags, Error> {
+        self.query::<u16>(&format!("{}:COND?", R::NODE))
+            .map(R::Flags::from)
+    }
+
+    /// Read and clear the event register of a status register set (`STATus:<node>[:EVENt]?`)
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn status_event<R: StatusRegister>(&mut self) -> Result<R::Flags, Error> {
+        self.query::<u16>(&format!("{}:EVEN?", R::NODE))
+            .map(R::Flags::from)
+    }
+
+    /// Read the enable register of a status register set (`STATus:<node>:ENABle?`)
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn status_enable<R: StatusRegister>(&mut self) -> Result<R::Flags, Error> {
+        self.query::<u16>(&format!("{}:ENAB?", R::NODE))
+            .map(R::Flags::from)
+    }
+
+    /// Set the enable register of a status register set (`STATus:<node>:ENABle`)
+    ///
+    /// Enabled events are summarized in the status byte
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn set_status_enable<R: StatusRegister>(&mut self, enable: R::Flags) -> Result<(), Error> {
+        let enable: u16 = enable.into();
+        self.write_string(&format!("{}:ENAB {enable}", R::NODE))
+    }
+
+    /// Read the transition filters of a status register set (`STATus:<node>:PTRansition?` and `NTRansition?`)
+    ///
+    /// Returns the positive and negative transition filters
+    ///
+    /// # Errors
+    /// Will return an error if the query fails or the response is not a valid register value
+    pub fn status_transition<R: StatusRegister>(&mut self) -> Result<(R::Flags, R::Flags), Error> {
+        self.transaction(|tx| {
+            let positive = tx.query::<u16>(&format!("{}:PTR?", R::NODE))?;
+            let negative = tx.query::<u16>(&format!("{}:NTR?", R::NODE))?;
+            Ok((positive.into(), negative.into()))
+        })
+    }
+
+    /// Set the transition filters of a status register set (`STATus:<node>:PTRansition` and `NTRansition`)
+    ///
+    /// A condition bit sets the matching event bit when it goes from 0 to 1 and is set in `positive`,
+    /// or when it goes from 1 to 0 and is set in `negative`
+    ///
+    /// # Errors
+    /// Will return an error if the commands cannot be written
+    pub fn set_status_transition<R: StatusRegister>(
+        &mut self,
+        positive: R::Flags,
+        negative: R::Flags,
+    ) -> Result<(), Error> {
+        let (positive, negative): (u16, u16) = (positive.into(), negative.into());
+        self.transaction(|tx| {
+            tx.write_string(&format!("{}:PTR {positive}", R::NODE))?;
+            tx.write_string(&format!("{}:NTR {negative}", R::NODE))
+        })
+    }
+
+    /// Reset the enable and transition filters of the SCPI status registers to their defaults (`STATus:PRESet`)
+    ///
+    /// # Errors
+    /// Will return an error if the command cannot be written
+    pub fn status_preset(&mut self) -> Result<(), Error> {
+        self.write_string("STAT:PRES")
+    }
+
+    /// Route events of a status register set to the SRQ line
+    ///
+    /// This sets the enable register of the register set, and adds its summary bit to the service request enable register.
+    ///
+    /// Transition filters are left untouched; by default, SCPI instruments report positive transitions only.
+    ///
+    /// # Errors
+    /// Will return an error if the registers cannot be configured
+    pub fn route_status_to_srq<R: StatusRegister>(
+        &mut self,
+        enable: R::Flags,
+    ) -> Result<(), Error> {
+        self.transaction(|tx| {
+            tx.set_status_enable::<R>(enable)?;
+
+            let mut sre = tx.service_request_enable()?;
+            sre.insert(R::SUMMARY);
+            tx.set_service_request_enable(sre)
+        })
+    }
+
+    /// Wait for a service request, then read the registers that caused it
+    ///
+    /// If `Event::ServiceReq` is not enabled with the `Queue` mechanism yet, it is enabled for the duration of the
+    /// wait; enable it beforehand not to miss service requests raised before the call.
+    ///
+    /// The status byte is read with a serial poll, and the event registers summarized by the set bits are read (and cleared).
+    /// Other threads can use the session during the wait, but not while the registers are read.
+    ///
+    /// # Errors
+    /// Will return an error if no service request occurs before the timeout, or the registers cannot be read
+    pub fn wait_service_request(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<StatusReport, Error> {
+        let mut queue = self.event_queue(event::Event::ServiceReq)?;
+        if queue.try_next(timeout)?.is_none() {
+            return Err(Error::new(bindings::VI_ERROR_TMO, Some(self.session_id())));
+        }
+        drop(queue);
+
+        self.transaction(|tx| {
+            let status_byte = tx.read_status()?;
+            StatusReport::decode(status_byte, |query| tx.query(query))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_register_nodes() {
+        assert_eq!(Operation::NODE, "STAT:OPER");
+        assert_eq!(Questionable::NODE, "STAT:QUES");
+        assert_eq!(Operation::SUMMARY.bits(), 0x80);
+        assert_eq!(Questionable::SUMMARY.bits(), 0x08);
+    }
+
+    #[test]
+    fn test_status_flags() {
+        // Bit assignments of SCPI 1999, volume 1, section 9
+        assert_eq!(OperationStatus::CALIBRATING.bits(), 1 << 0);
+        assert_eq!(OperationStatus::MEASURING.bits(), 1 << 4);
+        assert_eq!(OperationStatus::WAITING_FOR_TRIGGER.bits(), 1 << 5);
+        assert_eq!(OperationStatus::CORRECTING.bits(), 1 << 7);
+        assert_eq!(OperationStatus::INSTRUMENT_SUMMARY.bits(), 1 << 13);
+        assert_eq!(OperationStatus::PROGRAM_RUNNING.bits(), 1 << 14);
+
+        assert_eq!(QuestionableStatus::VOLTAGE.bits(), 1 << 0);
+        assert_eq!(QuestionableStatus::TEMPERATURE.bits(), 1 << 4);
+        assert_eq!(QuestionableStatus::CALIBRATION.bits(), 1 << 8);
+        assert_eq!(QuestionableStatus::INSTRUMENT_SUMMARY.bits(), 1 << 13);
+        assert_eq!(QuestionableStatus::COMMAND_WARNING.bits(), 1 << 14);
+
+        let status = QuestionableStatus::from(0x0011);
+        assert!(status.contains(QuestionableStatus::VOLTAGE | QuestionableStatus::TEMPERATURE));
+        assert_eq!(u16::from(status), 0x0011);
+        assert_eq!(
+            format!("{status:?}"),
+            "QuestionableStatus(VOLTAGE | TEMPERATURE)"
+        );
+    }
+
+    #[test]
+    fn test_status_report() {
+        let mut queries = vec![];
+        let status_byte = StatusByte::ESB | StatusByte::QSB | StatusByte::RQS;
+        let report = StatusReport::decode(status_byte, |query| {
+            queries.push(query.to_string());
+            Ok(match query {
+                "*ESR?" => 0x11,
+                _ => 0x0002,
+            })
+        })
+        .unwrap();
+
+        // Only the summarized registers are read
+        assert_eq!(queries, ["*ESR?", "STAT:QUES:EVEN?"]);
+        assert_eq!(
+            report,
+            StatusReport {
+                status_byte,
+                standard: Some(EventStatus::OPC | EventStatus::EXE),
+                operation: None,
+                questionable: Some(QuestionableStatus::CURRENT),
+            }
+        );
+
+        let report = StatusReport::decode(StatusByte::OSB, |_| Ok(0x0010)).unwrap();
+        assert_eq!(report.operation, Some(OperationStatus::MEASURING));
+        assert_eq!(report.standard, None);
+
+        let empty = StatusReport::decode(StatusByte::empty(), |_| unreachable!()).unwrap();
+        assert_eq!(empty, StatusReport::default());
+
+        let error = StatusReport::decode(StatusByte::ESB, |_| Ok(0x100));
+        assert!(error.is_err());
+    }
+}
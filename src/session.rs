@@ -3,41 +3,28 @@
     clippy::cast_possible_truncation,
     reason = "Needed for compatibility with the VISA library"
 )]
+#![expect(
+    clippy::cast_possible_wrap,
+    reason = "Needed for compatibility with the VISA library"
+)]
 
 use crate::{
     attribute::{self, AccessMode},
     bindings,
     error::Error,
-    event, ResourceManager,
+    event,
+    ieee4882::StatusByte,
+    text::TextOptions,
+    transaction::IoLock,
+    InstalledHandler, ResourceManager,
 };
 use std::{
-    future,
     io::Read,
     path::Path,
-    sync::{Arc, Mutex, OnceLock},
+    sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard},
     vec,
 };
 
-//------------ Async IO signal table -----------------------------------
-
-type IoSignalTable = std::collections::HashSet<(bindings::ViSession, bindings::ViJobId)>;
-fn io_signal_table() -> &'static Mutex<IoSignalTable> {
-    static TABLE: OnceLock<Mutex<IoSignalTable>> = OnceLock::new();
-    TABLE.get_or_init(|| Mutex::new(IoSignalTable::new()))
-}
-
-fn signal_done(session: bindings::ViSession, job_id: bindings::ViJobId) {
-    let mut table = io_signal_table().lock().unwrap();
-    table.insert((session, job_id));
-}
-
-fn check_signal_done(session: bindings::ViSession, job_id: bindings::ViJobId) -> bool {
-    let mut table = io_signal_table().lock().unwrap();
-    table.remove(&(session, job_id))
-}
-
-//----------------------------------------------------------------------
-
 /// Options for opening a session
 #[derive(Debug, Default, Clone, Copy)]
 pub struct SessionOptions {
@@ -52,12 +39,53 @@ pub struct SessionOptions {
     pub load_config: bool,
 }
 
+/// The VISA session shared by all clones of a `Session`
+///
+/// The session is closed when the handle is dropped, or by `SessionHandle::close`.
+#[derive(Debug)]
+struct SessionHandle {
+    vi: bindings::ViSession,
+    io_lock: Arc<IoLock>,
+
+    /// The text options, kept with the VISA attributes they mirror
+    text: RwLock<TextOptions>,
+
+    /// Whether the asynchronous I/O completion handler is installed, locked while installing it
+    async_io: Mutex<bool>,
+}
+impl SessionHandle {
+    fn close(mut self) -> Result<(), Error> {
+        let vi = std::mem::replace(&mut self.vi, bindings::VI_NULL);
+        Error::wrap_binding(Some(vi), || unsafe { bindings::viClose(vi) })?;
+        crate::async_io::forget_session(vi);
+        Ok(())
+    }
+}
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        // VI_NULL is never a valid session, and marks a handle that was already closed
+        if self.vi != bindings::VI_NULL {
+            unsafe { bindings::viClose(self.vi) };
+            crate::async_io::forget_session(self.vi);
+        }
+    }
+}
+
 /// A session to a resource
-#[derive(Debug, Clone, Default)]
+///
+/// Clones of a session share the same VISA session, which is closed once the last of them is dropped.
+/// Use `Session::duplicate` to open a second, independent VISA session to the same resource.
+#[derive(Debug, Clone)]
 pub struct Session {
-    vi: bindings::ViSession,
-    io_lock: Arc<Mutex<()>>,
+    handle: Arc<SessionHandle>,
 }
+
+// Sessions are shared between threads, by the event handlers and async tasks among others
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Session>();
+};
+
 impl Session {
     /// Open a session to a resource
     ///
@@ -93,32 +121,92 @@ impl Session {
             )
         })?;
 
-        let io_lock = Arc::new(Mutex::new(()));
-        Ok(Self { vi, io_lock })
+        Ok(Self::from_handle(vi))
+    }
+
+    fn from_handle(vi: bindings::ViSession) -> Self {
+        Self {
+            handle: Arc::new(SessionHandle {
+                vi,
+                io_lock: Arc::default(),
+                text: RwLock::default(),
+                async_io: Mutex::default(),
+            }),
+        }
+    }
+
+    /// A session that is not open, for tests that never reach VISA
+    #[cfg(test)]
+    pub(crate) fn null() -> Self {
+        Self::from_handle(bindings::VI_NULL)
+    }
+
+    /// Open a second VISA session to the same resource
+    ///
+    /// Unlike a clone, the new session has its own attributes, locks and event mechanisms.
+    /// NI-VISA needs separate sessions to handle one event type with both the queue and handler mechanisms.
+    /// The text options of this session are applied to the new one, along with the VISA attributes they set; other
+    /// VISA attributes are not copied.
+    ///
+    /// # Errors
+    /// Will return an error if the resource name cannot be read, or the session cannot be opened
+    pub fn duplicate(&self) -> Result<Self, Error> {
+        let rm = ResourceManager::new()?;
+        let name = self.get_attribute::<attribute::rsrc::RsrcName>()?;
+        let mut session = Self::new(&rm, &name, SessionOptions::default())?;
+        session.set_text_options(self.text_options())?;
+        Ok(session)
     }
 
     /// Get the raw session identifier
     #[must_use]
     pub fn session_id(&self) -> bindings::ViSession {
-        self.vi
+        self.handle.vi
+    }
+
+    /// The in-process lock on the I/O of the session, shared by its clones
+    pub(crate) fn io_lock(&self) -> &Arc<IoLock> {
+        &self.handle.io_lock
+    }
+
+    /// Whether the asynchronous I/O completion handler is installed, shared by the clones of the session
+    pub(crate) fn async_io_installed(&self) -> MutexGuard<'_, bool> {
+        self.handle
+            .async_io
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The text options of the session, shared by its clones
+    pub(crate) fn text(&self) -> RwLockReadGuard<'_, TextOptions> {
+        self.handle
+            .text
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the text options of the session and its clones, without updating the VISA attributes
+    pub(crate) fn set_text(&self, options: TextOptions) {
+        *self
+            .handle
+            .text
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = options;
     }
 
     /// Run a function with exclusive access to the session
     ///
-    /// # Panics
-    /// Panics if the lock cannot be acquired
+    /// See `Session::transaction` for sequences of fallible operations
     pub fn with_lock<T>(&self, f: impl FnOnce(&Self) -> T) -> T {
-        let _lock = self.io_lock.lock().unwrap();
+        let _lock = self.io_lock().acquire();
         f(self)
     }
 
     /// Run a function with exclusive access to the session
     ///
-    /// # Panics
-    /// Panics if the lock cannot be acquired
+    /// See `Session::transaction` for sequences of fallible operations
     pub fn with_lock_mut<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
-        let lock = self.io_lock.clone();
-        let _lock = lock.lock().unwrap();
+        let _lock = self.io_lock().acquire();
         f(self)
     }
 
@@ -132,8 +220,10 @@ impl Session {
 
     /// Reads the entire available data from the session into a string
     ///
+    /// The data is decoded, and the read termination stripped, according to `Session::text_options`
+    ///
     /// # Errors
-    /// Will return an error if the data cannot be read
+    /// Will return an error if the data cannot be read or decoded
     pub fn read_string(&mut self) -> Result<String, Error> {
         let mut buf = vec![];
         let mut chunk = [0u8; 8192];
@@ -147,18 +237,19 @@ impl Session {
             }
         }
 
-        let buf = String::from_utf8(buf).map_err(|_| Error::from_msg("Invalid UTF-8"))?;
-        Ok(buf)
+        self.text().decode_message(&buf)
     }
 
     /// Write a string to the session
     ///
+    /// The string is encoded, and the write termination appended, according to `Session::text_options`
+    ///
     /// # Errors
-    /// Will return an error if the data cannot be written
+    /// Will return an error if the data cannot be encoded or written
     pub fn write_string(&mut self, buf: &str) -> Result<(), Error> {
-        let with_terminator = format!("{buf}\n");
+        let with_terminator = self.text().encode_message(buf)?;
 
-        <Self as std::io::Write>::write(self, with_terminator.as_bytes())?;
+        <Self as std::io::Write>::write(self, &with_terminator)?;
         Ok(())
     }
 
@@ -175,6 +266,8 @@ impl Session {
 
     /// Write a query to the session and parse the response as a value
     ///
+    /// The query and its response are a transaction: other threads cannot use the session in between.
+    ///
     /// # Errors
     /// Will return an error if the query cannot be written or the response cannot be parsed
     pub fn query<T>(&mut self, cmd: &str) -> Result<T, Error>
@@ -182,11 +275,13 @@ impl Session {
         T: std::str::FromStr,
         T::Err: std::fmt::Debug,
     {
-        self.write_string(cmd)?;
-        self.read_string()?
-            .trim()
-            .parse()
-            .map_err(|e| Error::from_msg(format!("{cmd}: {e:?}")))
+        self.transaction(|tx| {
+            tx.write_string(cmd)?;
+            tx.read_string()?
+                .trim()
+                .parse()
+                .map_err(|e| Error::from_msg(format!("{cmd}: {e:?}")))
+        })
     }
 
     /// Clear the session
@@ -194,15 +289,25 @@ impl Session {
     /// # Errors
     /// Will return an error if the session cannot be cleared
     pub fn clear(&self) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe { bindings::viClear(self.vi) })
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viClear(self.handle.vi)
+        })
     }
 
-    /// Close the session
+    /// Close the session, reporting any error
+    ///
+    /// Dropping the last clone of a session closes it as well. If other clones are still alive, the session is left
+    /// open for them, and closed when the last of them is dropped.
     ///
     /// # Errors
-    /// Will return an error if the session cannot be closed
+    /// Will return an error if the session cannot be closed, or is still used by other clones
     pub fn close(self) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe { bindings::viClose(self.vi) })
+        match Arc::into_inner(self.handle) {
+            Some(handle) => handle.close(),
+            None => Err(Error::from_msg(
+                "Session is still in use by its clones, and will be closed with the last of them",
+            )),
+        }
     }
 
     /// Set the size of the read buffer
@@ -210,8 +315,8 @@ impl Session {
     /// # Errors
     /// Will return an error if the buffer size cannot be set
     pub fn set_read_buffer(&self, size: usize) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viSetBuf(self.vi, bindings::VI_READ_BUF as u16, size as u32)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viSetBuf(self.handle.vi, bindings::VI_READ_BUF as u16, size as u32)
         })
     }
 
@@ -220,8 +325,8 @@ impl Session {
     /// # Errors
     /// Will return an error if the buffer size cannot be set
     pub fn set_write_buffer(&self, size: usize) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viSetBuf(self.vi, bindings::VI_WRITE_BUF as u16, size as u32)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viSetBuf(self.handle.vi, bindings::VI_WRITE_BUF as u16, size as u32)
         })
     }
 
@@ -230,9 +335,9 @@ impl Session {
     /// # Errors
     /// Will return an error if the buffer size cannot be set
     pub fn set_rw_buffer(&self, size: usize) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
             bindings::viSetBuf(
-                self.vi,
+                self.handle.vi,
                 bindings::VI_READ_BUF as u16 | bindings::VI_WRITE_BUF as u16,
                 size as u32,
             )
@@ -244,8 +349,8 @@ impl Session {
     /// # Errors
     /// Will return an error if the buffer size cannot be set
     pub fn set_io_in_buffer(&self, size: usize) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viSetBuf(self.vi, bindings::VI_IO_IN_BUF as u16, size as u32)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viSetBuf(self.handle.vi, bindings::VI_IO_IN_BUF as u16, size as u32)
         })
     }
 
@@ -254,8 +359,8 @@ impl Session {
     /// # Errors
     /// Will return an error if the buffer size cannot be set
     pub fn set_io_out_buffer(&self, size: usize) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viSetBuf(self.vi, bindings::VI_IO_OUT_BUF as u16, size as u32)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viSetBuf(self.handle.vi, bindings::VI_IO_OUT_BUF as u16, size as u32)
         })
     }
 
@@ -264,9 +369,9 @@ impl Session {
     /// # Errors
     /// Will return an error if the buffer size cannot be set
     pub fn set_io_buffer(&self, size: usize) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
             bindings::viSetBuf(
-                self.vi,
+                self.handle.vi,
                 bindings::VI_IO_IN_BUF as u16 | bindings::VI_IO_OUT_BUF as u16,
                 size as u32,
             )
@@ -279,8 +384,13 @@ impl Session {
     /// Will return an error if the data cannot be written
     pub fn buffer_write(&self, buf: &[u8]) -> Result<(), Error> {
         let mut written = 0;
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viBufWrite(self.vi, buf.as_ptr(), buf.len() as u32, &raw mut written)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viBufWrite(
+                self.handle.vi,
+                buf.as_ptr(),
+                buf.len() as u32,
+                &raw mut written,
+            )
         })?;
         Ok(())
     }
@@ -292,8 +402,8 @@ impl Session {
     pub fn buffer_read(&self, len: usize) -> Result<Vec<u8>, Error> {
         let mut buf = vec![0u8; len];
         let mut read = 0;
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viBufRead(self.vi, buf.as_mut_ptr(), len as u32, &raw mut read)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viBufRead(self.handle.vi, buf.as_mut_ptr(), len as u32, &raw mut read)
         })?;
         buf.resize(read as usize, 0);
         Ok(buf)
@@ -349,8 +459,8 @@ impl Session {
 
     #[allow(clippy::trivially_copy_pass_by_ref)]
     fn flush_inner(&self, mask: u32) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viFlush(self.vi, mask as u16)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viFlush(self.handle.vi, mask as u16)
         })
     }
 
@@ -392,8 +502,8 @@ impl Session {
     /// # Errors
     /// Will return an error if the trigger cannot be asserted
     pub fn assert_trigger(&self, protocol: TriggerProtocol) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viAssertTrigger(self.vi, protocol as u16)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viAssertTrigger(self.handle.vi, protocol as u16)
         })
     }
 
@@ -433,16 +543,18 @@ impl Session {
     ///
     /// Manufacturers of 488.2 devices use the remaining lower-order bits to communicate the reason for the service request or to summarize the device state.
     ///
+    /// See `StatusByte` for the named bits, and `Session::status_byte` for the `*STB?` equivalent.
+    ///
     /// # Errors
     /// Will return an error if the status byte cannot be read
-    pub fn read_status(&self) -> Result<u8, Error> {
+    pub fn read_status(&self) -> Result<StatusByte, Error> {
         let mut status: u16 = 0;
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viReadSTB(self.vi, &raw mut status)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viReadSTB(self.handle.vi, &raw mut status)
         })?;
 
         // Upper 8 bits are always 0, safe to cast to u8
-        Ok(status as u8)
+        Ok(StatusByte::from_bits(status as u8))
     }
 
     //=========================================================================
@@ -499,9 +611,9 @@ impl Session {
     /// Will return an error if the attribute cannot be read
     pub unsafe fn get_attribute_raw<T>(&self, attr: bindings::ViAttr) -> Result<T, Error> {
         let mut value: T = std::mem::zeroed::<T>();
-        Error::wrap_binding(Some(self.vi), || unsafe {
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
             let value = (&raw mut value).cast::<std::ffi::c_void>();
-            bindings::viGetAttribute(self.vi, attr, value)
+            bindings::viGetAttribute(self.handle.vi, attr, value)
         })?;
         Ok(value)
     }
@@ -520,8 +632,8 @@ impl Session {
         attr: bindings::ViAttr,
         value: bindings::ViAttrState,
     ) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viSetAttribute(self.vi, attr, value)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viSetAttribute(self.handle.vi, attr, value)
         })
     }
 
@@ -531,13 +643,15 @@ impl Session {
 
     /// Create an exclusive lock on the session
     ///
+    /// The lock is held until `Session::unlock` is called; `Session::lock_exclusive` releases it automatically.
+    ///
     /// # Errors
     /// Will return an error if the lock cannot be acquired
     pub fn lock(&self, lock_timeout: std::time::Duration) -> Result<(), Error> {
         let lock_timeout = lock_timeout.as_millis() as u32;
-        Error::wrap_binding(Some(self.vi), || unsafe {
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
             bindings::viLock(
-                self.vi,
+                self.handle.vi,
                 bindings::VI_EXCLUSIVE_LOCK,
                 lock_timeout,
                 std::ptr::null_mut(),
@@ -546,43 +660,16 @@ impl Session {
         })
     }
 
-    /// Create a shared lock on the session
-    ///
-    /// # Errors
-    /// Will return an error if the lock cannot be acquired
-    pub fn lock_shared(
-        &self,
-        lock_timeout: std::time::Duration,
-        requested_key: &str,
-    ) -> Result<String, Error> {
-        let lock_timeout = lock_timeout.as_millis() as u32;
-        let requested_key = std::ffi::CString::new(requested_key)?;
-        let mut actual_key = vec![std::ffi::c_char::default(); 256];
-
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viLock(
-                self.vi,
-                bindings::VI_SHARED_LOCK,
-                lock_timeout,
-                requested_key.as_ptr(),
-                actual_key.as_mut_ptr(),
-            )
-        })?;
-
-        //
-        // Turn the c_char vec back into a string
-        let key = unsafe { std::ffi::CStr::from_ptr(actual_key.as_ptr()) }
-            .to_str()
-            .map_err(|_| Error::from_msg("Invalid lock key"))?;
-        Ok(key.to_string())
-    }
-
     /// Unlock the session
     ///
+    /// Releases one level of nested locks.
+    ///
     /// # Errors
     /// Will return an error if the session cannot be unlocked
     pub fn unlock(&self) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe { bindings::viUnlock(self.vi) })
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viUnlock(self.handle.vi)
+        })
     }
 
     //=========================================================================
@@ -617,8 +704,8 @@ impl Session {
         mechanism: event::HandlingMechanism,
         filter: bindings::ViEventFilter,
     ) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viEnableEvent(self.vi, event_type as u32, mechanism as u16, filter)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viEnableEvent(self.handle.vi, event_type as u32, mechanism as u16, filter)
         })
     }
 
@@ -643,8 +730,8 @@ impl Session {
         event_type: event::Event,
         mechanism: event::HandlingMechanism,
     ) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viDisableEvent(self.vi, event_type as u32, mechanism as u16)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viDisableEvent(self.handle.vi, event_type as u32, mechanism as u16)
         })
     }
 
@@ -667,20 +754,21 @@ impl Session {
         event_type: event::Event,
         mechanism: event::HandlingMechanism,
     ) -> Result<(), Error> {
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viDiscardEvents(self.vi, event_type as u32, mechanism as u16)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viDiscardEvents(self.handle.vi, event_type as u32, mechanism as u16)
         })
     }
 
     /// The `viInstallHandler()` operation allows applications to install handlers on sessions.
     ///
-    /// You can use the `define_event_handler` macro to define a handler if you don't need to pass user data.
+    /// To install a closure instead of a `Handler` implementation, use `Session::on_event`.
     ///
     /// The handler specified in the handler parameter is installed along with any previously installed handlers for the specified event.
     ///
-    /// Applications can specify a value in the userHandle parameter that is passed to the handler on its invocation.
+    /// The handler receives a reference to `user_data` on each invocation. The data is owned by the returned
+    /// `InstalledHandler`, which uninstalls the handler when dropped, so it outlives every invocation.
     ///
-    /// VISA identifies handlers uniquely using the handler reference and this value.
+    /// VISA identifies handlers uniquely using the handler reference and the address of the data.
     ///
     /// VISA allows applications to install multiple handlers for an eventType on the same session.
     ///
@@ -694,56 +782,18 @@ impl Session {
     ///
     /// # Errors
     /// Will return an error if the handler cannot be installed
-    pub fn add_event_handler<'data, H: event::HandlerWithData>(
-        &'data self,
-        event_type: event::Event,
-        user_data: Option<&'data mut H::Data>,
-    ) -> Result<(), Error> {
-        let context = match user_data {
-            Some(mut user_data) => {
-                let data = &raw mut user_data;
-                data.cast::<std::ffi::c_void>()
-            }
-            None => std::ptr::null_mut(),
-        };
-
-        let handler = H::into();
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viInstallHandler(self.vi, event_type as u32, handler, context)
-        })
-    }
-
-    /// The `viUninstallHandler()` operation allows applications to uninstall handlers for events on sessions.
-    ///
-    /// Applications should also specify the value in the userHandle parameter that was passed while installing the handler.
-    ///
-    /// VISA identifies handlers uniquely using the handler reference and this value.
-    ///
-    /// All the handlers, for which the handler reference and the value matches, are uninstalled.
-    ///
-    /// Specifying `VI_ANY_HNDLR` as the value for the handler parameter causes the operation to uninstall all
-    ///
-    /// the handlers with the matching value in the userHandle parameter.
-    ///
-    /// # Errors
-    /// Will return an error if the handler cannot be removed
-    pub fn remove_event_handler<'data, H: event::HandlerWithData>(
-        &'data self,
+    pub fn add_event_handler<H>(
+        &self,
         event_type: event::Event,
-        user_data: Option<&'data mut H::Data>,
-    ) -> Result<(), Error> {
-        let context = match user_data {
-            Some(mut user_data) => {
-                let data = &raw mut user_data;
-                data.cast::<std::ffi::c_void>()
-            }
-            None => std::ptr::null_mut(),
-        };
-
-        let handler = H::into();
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viUninstallHandler(self.vi, event_type as u32, handler, context)
-        })
+        user_data: Option<Box<H::Data>>,
+    ) -> Result<InstalledHandler<H>, Error>
+    where
+        H: event::HandlerWithData,
+        H::Data: Send + Sync,
+    {
+        let mut handler = InstalledHandler::uninstalled(self.clone(), event_type, user_data);
+        handler.install()?;
+        Ok(handler)
     }
 
     /// Waits for an occurrence of the specified event for a given session.
@@ -762,7 +812,7 @@ impl Session {
     ///
     /// If the specified timeout value is `VI_TMO_IMMEDIATE`, the operation is not suspended; therefore, this value can be used to dequeue events from an event queue.
     ///
-    /// When the outContext handle returned from a successful invocation of `viWaitOnEvent()` is no longer needed, it should be passed to `viClose()`.
+    /// The returned `EventContext` reports the type of the event that occurred, and is closed when dropped.
     ///
     /// If a session's event queue becomes full and a new event arrives, the new event is discarded.
     ///
@@ -772,79 +822,54 @@ impl Session {
     ///
     /// attribute `VI_ATTR_MAX_QUEUE_LENGTH` to the required size.
     ///
+    /// An overflow of the queue is not reported as an error; `Session::event_queue` counts them.
+    ///
     /// # Errors
     /// Will return an error if the event cannot be waited on
     pub fn wait_on_event(
         &self,
         in_event_type: event::Event,
         timeout: std::time::Duration,
-    ) -> Result<bindings::ViEvent, Error> {
+    ) -> Result<event::EventContext, Error> {
+        self.wait_on_event_status(in_event_type, timeout)
+            .map(|(context, _)| context)
+    }
+
+    /// Wait for an event, keeping the completion code
+    ///
+    /// `VI_WARN_QUEUE_OVERFLOW` is returned along with a valid event, and is not treated as an error
+    pub(crate) fn wait_on_event_status(
+        &self,
+        in_event_type: event::Event,
+        timeout: std::time::Duration,
+    ) -> Result<(event::EventContext, bindings::ViStatus), Error> {
+        let mut event_type: bindings::ViEventType = 0;
         let mut context: bindings::ViEvent = bindings::ViEvent::default();
-        Error::wrap_binding(Some(self.vi), || unsafe {
+        let status = unsafe {
             bindings::viWaitOnEvent(
-                self.vi,
+                self.handle.vi,
                 in_event_type as u32,
                 timeout.as_millis() as u32,
-                std::ptr::null_mut(),
+                &raw mut event_type,
                 &raw mut context,
             )
-        })?;
+        };
+        if status != bindings::VI_WARN_QUEUE_OVERFLOW as i32 {
+            Error::wrap_binding(Some(self.handle.vi), || status)?;
+        }
 
-        Ok(context)
+        // Event types this crate does not know are reported as the requested type
+        let event_type = event::Event::try_from(event_type).unwrap_or(in_event_type);
+        Ok((
+            event::EventContext::owned(self.handle.vi, context, event_type),
+            status,
+        ))
     }
 
     //=========================================================================
     // Async and file I/O
     //=========================================================================
 
-    /// Create an asynchronous read task that can be awaited or terminated
-    ///
-    /// # Errors
-    /// Will return an error if the data cannot be read from the session
-    pub fn read_async(
-        &self,
-        bytes: usize,
-        timeout: std::time::Duration,
-    ) -> Result<AsyncTask, Error> {
-        let mut jobid = bindings::ViJobId::default();
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viReadAsync(self.vi, std::ptr::null_mut(), bytes as u32, &raw mut jobid)
-        })?;
-
-        self.add_event_handler::<AsyncTask>(event::Event::IoCompletion, Some(&mut jobid))?;
-
-        Ok(AsyncTask {
-            session: self.vi,
-            job_id: jobid,
-            started: std::time::Instant::now(),
-            timeout,
-        })
-    }
-
-    /// Create an asynchronous write task that can be awaited or terminated
-    ///
-    /// # Errors
-    /// Will return an error if the data cannot be written to the session
-    pub fn write_async(
-        &self,
-        buf: &[u8],
-        timeout: std::time::Duration,
-    ) -> Result<AsyncTask, Error> {
-        let mut jobid = bindings::ViJobId::default();
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viWriteAsync(self.vi, buf.as_ptr(), buf.len() as u32, &raw mut jobid)
-        })?;
-
-        self.add_event_handler::<AsyncTask>(event::Event::IoCompletion, Some(&mut jobid))?;
-
-        Ok(AsyncTask {
-            session: self.vi,
-            job_id: jobid,
-            started: std::time::Instant::now(),
-            timeout,
-        })
-    }
-
     /// Take data from a file and write it out synchronously.
     /// If size is None, the entire file is read.
     ///
@@ -861,8 +886,13 @@ impl Session {
         let filename = std::ffi::CString::new(filename.as_bytes())?;
 
         let mut written = 0;
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viWriteFromFile(self.vi, filename.as_ptr(), size as u32, &raw mut written)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viWriteFromFile(
+                self.handle.vi,
+                filename.as_ptr(),
+                size as u32,
+                &raw mut written,
+            )
         })
     }
 
@@ -875,8 +905,13 @@ impl Session {
         let filename = std::ffi::CString::new(filename.as_bytes())?;
 
         let mut written = 0;
-        Error::wrap_binding(Some(self.vi), || unsafe {
-            bindings::viReadToFile(self.vi, filename.as_ptr(), size as u32, &raw mut written)
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
+            bindings::viReadToFile(
+                self.handle.vi,
+                filename.as_ptr(),
+                size as u32,
+                &raw mut written,
+            )
         })
     }
 }
@@ -886,9 +921,9 @@ impl std::io::Read for Session {
         std::thread::sleep(std::time::Duration::from_millis(500));
 
         let mut bytes_read = 0;
-        Error::wrap_binding(Some(self.vi), || unsafe {
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
             bindings::viRead(
-                self.vi,
+                self.handle.vi,
                 buf.as_mut_ptr(),
                 buf.len() as u32,
                 &raw mut bytes_read,
@@ -905,9 +940,9 @@ impl std::io::Write for Session {
         std::thread::sleep(std::time::Duration::from_millis(500));
 
         let mut bytes_written = 0;
-        Error::wrap_binding(Some(self.vi), || unsafe {
+        Error::wrap_binding(Some(self.handle.vi), || unsafe {
             bindings::viWrite(
-                self.vi,
+                self.handle.vi,
                 buf.as_ptr(),
                 buf.len() as u32,
                 &raw mut bytes_written,
@@ -945,133 +980,9 @@ pub enum TriggerProtocol {
     Unreserve = bindings::VI_TRIG_PROT_UNRESERVE as u16,
 }
 
-/// An asynchronous task that can be awaited or terminated
-#[derive(Debug)]
-pub struct AsyncTask {
-    session: bindings::ViSession,
-    job_id: bindings::ViJobId,
-    started: std::time::Instant,
-    timeout: std::time::Duration,
-}
-impl AsyncTask {
-    /// Terminate the async task before it completes
-    /// This will cause the task to return `VI_ERROR_ABORT`
-    ///
-    /// # Errors
-    /// Will return an error if the task cannot be terminated
-    pub fn terminate(mut self) -> Result<(), Error> {
-        self.terminate_task()
-    }
-
-    fn terminate_task(&mut self) -> Result<(), Error> {
-        // Terminate running task
-        Error::wrap_binding(Some(self.session), || unsafe {
-            bindings::viTerminate(self.session, 0, self.job_id)
-        })?;
-
-        // Remove event handler
-        let context = (&raw mut self.job_id).cast::<std::ffi::c_void>();
-        let handler = <Self as event::HandlerWithData>::into();
-        Error::wrap_binding(Some(self.session), || unsafe {
-            bindings::viUninstallHandler(
-                self.session,
-                event::Event::IoCompletion as u32,
-                handler,
-                context,
-            )
-        })
-    }
-}
-impl future::Future for AsyncTask {
-    type Output = Result<(), Error>;
-
-    fn poll(
-        self: std::pin::Pin<&mut Self>,
-        _: &mut std::task::Context,
-    ) -> std::task::Poll<Self::Output> {
-        let this = self.get_mut();
-        if check_signal_done(this.session, this.job_id) {
-            std::task::Poll::Ready(Ok(()))
-        } else if this.started.elapsed() > this.timeout {
-            std::task::Poll::Ready(Err(Error {
-                status: crate::error::ErrorType::Tmo,
-                description: Some("Async IO Timeout".to_string()),
-            }))
-        } else {
-            std::task::Poll::Pending
-        }
-    }
-}
-impl event::HandlerWithData for AsyncTask {
-    type Data = bindings::ViJobId;
-    fn handle(
-        session: bindings::ViSession,
-        _: event::Event,
-        _: bindings::ViEvent,
-        job_id: &Self::Data,
-    ) -> Result<(), Error> {
-        signal_done(session, *job_id);
-        Ok(())
-    }
-}
-impl Drop for AsyncTask {
-    fn drop(&mut self) {
-        self.terminate_task().ok();
-    }
-}
-
-/// Formats a string and sends it to the session device
-/// For options, see <https://www.ni.com/docs/en-US/bundle/ni-visa-api-ref/page/ni-visa-api-ref/viprintf.html>
-///
-/// **IMPORTANT** %s expects null terminated c-strings!
-#[macro_export]
-macro_rules! printf {
-    ($session:expr, $format:expr $(, $($arg:expr),*)? $(,)?) => {
-        {
-            let session: &$crate::Session = $session;
-            let format: &str = $format;
-
-            match std::ffi::CString::new(format) {
-                Ok(cstr) => {
-                    $crate::error::Error::wrap_binding(Some(session.session_id()), || $crate::variadic_unsafe_nightmare_spaghetti!(
-                        $crate::bindings::viPrintf,
-                        args = [session.session_id(), cstr.as_ptr()],
-                        va_args = [$($arg),*]
-                    ))
-                },
-                Err(e) => Err(e.into())
-            }
-        }
-    };
-}
-
-/// Reads formatted data from the session device
-/// For options, see <https://www.ni.com/docs/en-US/bundle/ni-visa-api-ref/page/ni-visa-api-ref/viscanf.html>
-///
-/// **IMPORTANT** %s expects null terminated c-strings!
-#[macro_export]
-macro_rules! scanf {
-    ($session:expr, $format:expr, $($arg:path),*) => {
-        {
-            let session: &$crate::Session = $session;
-            let format: &str = $format;
-
-            match std::ffi::CString::new(format) {
-                Ok(cstr) => {
-                    $crate::error::Error::wrap_binding(Some(session.session_id()), || $crate::variadic_unsafe_nightmare_spaghetti!(
-                        $crate::bindings::viScanf,
-                        args = [session.session_id(), cstr.as_ptr()],
-                        va_args = [$(&mut $arg),*]
-                    ))
-                },
-                Err(e) => Err(e.into())
-            }
-        }
-    };
-}
-
 #[cfg(test)]
 mod test {
+    use super::*;
     use crate::{
         attribute::{misc::TmoValue, usb::UsbSerialNum, AsViReadable, AsViWritable},
         get_local_device,
@@ -1113,4 +1024,24 @@ mod test {
             TmoValue::write(session, Duration::from_secs(2)).unwrap();
         });
     }
+
+    #[test]
+    fn test_shared_close() {
+        let session = Session::null();
+        let clone = session.clone();
+        assert_eq!(clone.session_id(), session.session_id());
+
+        // The session stays open for the remaining clone
+        assert!(session.close().is_err());
+        assert_eq!(Arc::strong_count(&clone.handle), 1);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let session = get_local_device();
+        let mut duplicate = session.duplicate().unwrap();
+        assert_ne!(duplicate.session_id(), session.session_id());
+        session.close().unwrap();
+        assert!(!duplicate.idn().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,254 @@
+//! Memory allocated from a device or servant memory pool
+//!
+//! `Session::alloc_memory` allocates a block with `viMemAlloc`, and returns a `SharedMemory` freeing it when dropped.
+//! On a VXI/VME servant or MEMACC session the block is in the local memory shared with the bus, and on a PXI session
+//! it is host memory that devices can DMA into, accessed through `AddressSpace::PxiAlloc`:
+//! ```ignore
+//! let buffer = session.alloc_memory(4096)?;
+//! let (space, offset) = buffer.location();
+//! session.poke_reg(AddressSpace::PxiBar0, DMA_ADDRESS, offset as u32)?;
+//! // ... start the transfer on the device
+//! let mut samples = vec![0u32; 1024];
+//! buffer.read_block(0, &mut samples)?;
+//! ```
+//!
+//! The block is accessed with the register operations of the session, with offsets relative to its start and
+//! checked against its size.
+
+use crate::{
+    attribute::misc::{InterfaceType, IntfType, MemSpace},
+    bindings,
+    error::{Error, ErrorType},
+    AddressSpace, MappedWindow, RegisterValue, Session,
+};
+
+/// A block of device or servant memory, freed when dropped
+#[derive(Debug)]
+#[must_use = "Dropping the memory frees it"]
+pub struct SharedMemory {
+    session: Session,
+    space: AddressSpace,
+    offset: u64,
+    size: usize,
+
+    // Whether the block was allocated with `viMemAllocEx`, and must be freed with `viMemFreeEx`
+    extended: bool,
+    freed: bool,
+}
+impl SharedMemory {
+    /// The session the memory was allocated on
+    #[must_use]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// The address space of the memory, and the offset of its start, to pass to devices or block moves
+    #[must_use]
+    pub fn location(&self) -> (AddressSpace, u64) {
+        (self.space, self.offset)
+    }
+
+    /// The address space of the memory
+    #[must_use]
+    pub fn space(&self) -> AddressSpace {
+        self.space
+    }
+
+    /// The offset of the start of the memory in its address space
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The size of the memory, in bytes
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Read a single value at `offset` in the memory
+    ///
+    /// # Errors
+    /// Will return an error if the value is not entirely inside the memory, or cannot be read
+    pub fn read<T: RegisterValue>(&self, offset: usize) -> Result<T, Error> {
+        let offset = self.bus_offset::<T>(offset, 1)?;
+        self.session.peek_reg(self.space, offset)
+    }
+
+    /// Write a single value at `offset` in the memory
+    ///
+    /// # Errors
+    /// Will return an error if the value is not entirely inside the memory, or cannot be written
+    pub fn write<T: RegisterValue>(&self, offset: usize, value: T) -> Result<(), Error> {
+        let offset = self.bus_offset::<T>(offset, 1)?;
+        self.session.poke_reg(self.space, offset, value)
+    }
+
+    /// Read `buffer.len()` consecutive values starting at `offset` in the memory
+    ///
+    /// # Errors
+    /// Will return an error if the block is not entirely inside the memory, or cannot be read
+    pub fn read_block<T: RegisterValue>(
+        &self,
+        offset: usize,
+        buffer: &mut [T],
+    ) -> Result<(), Error> {
+        let offset = self.bus_offset::<T>(offset, buffer.len())?;
+        self.session.move_in(self.space, offset, buffer)
+    }
+
+    /// Write `data` to consecutive values starting at `offset` in the memory
+    ///
+    /// # Errors
+    /// Will return an error if the block is not entirely inside the memory, or cannot be written
+    pub fn write_block<T: RegisterValue>(&self, offset: usize, data: &[T]) -> Result<(), Error> {
+        let offset = self.bus_offset::<T>(offset, data.len())?;
+        self.session.move_out(self.space, offset, data)
+    }
+
+    /// Map the whole memory into the process, to access it with `MappedWindow::peek` and `MappedWindow::poke`
+    ///
+    /// # Errors
+    /// Will return an error if the session already has a mapped window, or the memory cannot be mapped
+    pub fn map(&self) -> Result<MappedWindow, Error> {
+        self.session.map_window(self.space, self.offset, self.size)
+    }
+
+    /// Free the memory, reporting any error
+    ///
+    /// # Errors
+    /// Will return an error if the memory cannot be freed
+    pub fn free(mut self) -> Result<(), Error> {
+        self.release()
+    }
+
+    /// The offset in the address space of `count` values of type `T` starting at `offset` in the memory
+    fn bus_offset<T: RegisterValue>(&self, offset: usize, count: usize) -> Result<u64, Error> {
+        let end = count
+            .checked_mul(usize::from(T::WIDTH))
+            .and_then(|length| length.checked_add(offset));
+        if end.is_none_or(|end| end > self.size) {
+            return Err(Error::new(bindings::VI_ERROR_INV_OFFSET, None));
+        }
+        Ok(self.offset + offset as u64)
+    }
+
+    fn release(&mut self) -> Result<(), Error> {
+        if self.freed {
+            return Ok(());
+        }
+        self.freed = true;
+
+        let vi = self.session.session_id();
+        let offset = self.offset;
+        let extended = self.extended;
+        Error::wrap_binding(Some(vi), || unsafe {
+            match bindings::ViBusAddress::try_from(offset) {
+                Ok(offset) if !extended => bindings::viMemFree(vi, offset),
+                _ => bindings::viMemFreeEx(vi, offset),
+            }
+        })
+    }
+}
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        self.release().ok();
+    }
+}
+
+impl Session {
+    /// Allocate `size` bytes from the memory of the device or servant
+    ///
+    /// The memory is allocated with `viMemAllocEx`, or `viMemAlloc` on VISA implementations not supporting it.
+    /// Its address space is `AddressSpace::PxiAlloc` on PXI sessions, and the `MemSpace` of the session otherwise.
+    ///
+    /// # Errors
+    /// Will return an error if the address space cannot be read, or the memory cannot be allocated
+    pub fn alloc_memory(&self, size: usize) -> Result<SharedMemory, Error> {
+        let space = match self.get_attribute::<IntfType>()? {
+            InterfaceType::Pxi => AddressSpace::PxiAlloc,
+            _ => self.get_attribute::<MemSpace>()?,
+        };
+        let alloc_size = bindings::ViBusSize::try_from(size)
+            .map_err(|_| Error::new(bindings::VI_ERROR_INV_SIZE, None))?;
+
+        let vi = self.session_id();
+        let mut offset: bindings::ViBusAddress64 = 0;
+        let mut extended = true;
+        let allocated = Error::wrap_binding(Some(vi), || unsafe {
+            bindings::viMemAllocEx(vi, alloc_size, &raw mut offset)
+        });
+        match allocated {
+            Err(e) if e.status == ErrorType::NsupOper => {
+                let mut offset32: bindings::ViBusAddress = 0;
+                Error::wrap_binding(Some(vi), || unsafe {
+                    bindings::viMemAlloc(vi, alloc_size, &raw mut offset32)
+                })?;
+                offset = u64::from(offset32);
+                extended = false;
+            }
+            result => result?,
+        }
+
+        Ok(SharedMemory {
+            session: self.clone(),
+            space,
+            offset,
+            size,
+            extended,
+            freed: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::register::test::get_register_device;
+
+    #[test]
+    fn test_bus_offset() {
+        let memory = SharedMemory {
+            session: Session::null(),
+            space: AddressSpace::PxiAlloc,
+            offset: 0x8000,
+            size: 0x100,
+            extended: true,
+            freed: true,
+        };
+
+        assert_eq!(memory.bus_offset::<u32>(0xFC, 1).unwrap(), 0x80FC);
+        assert_eq!(memory.bus_offset::<u16>(0x00, 0x80).unwrap(), 0x8000);
+        assert_eq!(memory.bus_offset::<u8>(0x100, 0).unwrap(), 0x8100);
+
+        let error = memory.bus_offset::<u32>(0xFE, 1).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvOffset);
+        assert!(memory.bus_offset::<u16>(0x02, 0x80).is_err());
+        assert!(memory.bus_offset::<u64>(0x00, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_shared_memory() {
+        let session = get_register_device();
+        let memory = session.alloc_memory(0x100).unwrap();
+        assert_eq!(memory.size(), 0x100);
+
+        memory.write(0x10, 0x1234_5678u32).unwrap();
+        assert_eq!(memory.read::<u32>(0x10).unwrap(), 0x1234_5678);
+
+        let data = [1u16, 2, 3, 4];
+        memory.write_block(0x20, &data).unwrap();
+        let mut buffer = [0u16; 4];
+        memory.read_block(0x20, &mut buffer).unwrap();
+        assert_eq!(buffer, data);
+
+        // Moves between the memory and the device use its location
+        let (space, offset) = memory.location();
+        session
+            .move_between::<u16, u16>((space, offset + 0x20), (space, offset + 0x40), 4)
+            .unwrap();
+        assert_eq!(memory.read::<u16>(0x46).unwrap(), 4);
+
+        memory.free().unwrap();
+    }
+}
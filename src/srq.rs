@@ -0,0 +1,244 @@
+//! Service requests shared by several devices on a GPIB bus
+//!
+//! All devices on a GPIB bus assert the same SRQ line. `SrqDispatcher` listens for `Event::ServiceReq` on the GPIB
+//! INTFC session, serial polls every registered device to find the ones requesting service, and calls their
+//! callbacks with the status byte they returned:
+//! ```ignore
+//! let dispatcher = SrqDispatcher::new(Session::new(&rm, "GPIB0::INTFC", SessionOptions::default())?)?;
+//! let scope = dispatcher.register(scope_session, |status| println!("Scope: {status:?}"));
+//! let dmm = dispatcher.register(dmm_session, |status| println!("DMM: {status:?}"));
+//! ```
+//!
+//! Serial polling a device clears its RQS bit, so each request is reported to exactly one callback.
+//! Polling continues while `GpibSrqState` shows the line as asserted, to catch requests raised in the meantime.
+
+use crate::{
+    attribute::{gpib::GpibSrqState, State},
+    error::Error,
+    event::Event,
+    ieee4882::StatusByte,
+    HandlerGuard, Session,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Maximum number of polling rounds per service request, in case a device keeps the line asserted
+const MAX_ROUNDS: usize = 8;
+
+type SrqCallback = Box<dyn FnMut(StatusByte) + Send>;
+
+/// Identifies a device registered with an `SrqDispatcher`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SrqDeviceId(usize);
+
+struct Device {
+    id: SrqDeviceId,
+    session: Session,
+    callback: SrqCallback,
+}
+impl std::fmt::Debug for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Device")
+            .field("id", &self.id)
+            .field("session", &self.session)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The registered devices, and the last error raised while polling them
+#[derive(Debug, Default)]
+struct Devices {
+    next_id: usize,
+    registered: Vec<Device>,
+    last_error: Option<Error>,
+}
+impl Devices {
+    /// Serial poll every device once, and call the callbacks of the ones requesting service
+    fn poll_all(&mut self, mut poll: impl FnMut(&Session) -> Result<StatusByte, Error>) -> usize {
+        let mut serviced = 0;
+        for device in &mut self.registered {
+            match poll(&device.session) {
+                Ok(status) if status.contains(StatusByte::RQS) => {
+                    (device.callback)(status);
+                    serviced += 1;
+                }
+                Ok(_) => {}
+                Err(e) => self.last_error = Some(e),
+            }
+        }
+        serviced
+    }
+}
+
+fn lock(devices: &Mutex<Devices>) -> MutexGuard<'_, Devices> {
+    devices
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Poll the devices until the SRQ line is released, returning the number of requests serviced
+fn service(interface: &Session, devices: &mut Devices) -> usize {
+    let mut serviced = 0;
+    for _ in 0..MAX_ROUNDS {
+        serviced += devices.poll_all(Session::read_status);
+        match interface.get_attribute::<GpibSrqState>() {
+            Ok(State::Asserted) => {}
+            Ok(_) => break,
+            Err(e) => {
+                devices.last_error = Some(e);
+                break;
+            }
+        }
+    }
+    serviced
+}
+
+/// Dispatches the service requests of a GPIB bus to the devices raising them
+///
+/// Callbacks run on a VISA thread, and must not register or unregister devices.
+/// Dropping the dispatcher stops listening for service requests.
+#[derive(Debug)]
+pub struct SrqDispatcher {
+    guard: HandlerGuard,
+    interface: Session,
+    devices: Arc<Mutex<Devices>>,
+}
+impl SrqDispatcher {
+    /// Start listening for service requests on a GPIB INTFC session
+    ///
+    /// # Errors
+    /// Will return an error if the service request event cannot be enabled on the interface
+    pub fn new(interface: Session) -> Result<Self, Error> {
+        let devices = Arc::new(Mutex::new(Devices::default()));
+        let guard = {
+            let polled = interface.clone();
+            let devices = devices.clone();
+            interface.on_event(Event::ServiceReq, move |_| {
+                service(&polled, &mut lock(&devices));
+            })?
+        };
+
+        Ok(Self {
+            guard,
+            interface,
+            devices,
+        })
+    }
+
+    /// The GPIB INTFC session the dispatcher listens on
+    #[must_use]
+    pub fn interface(&self) -> &Session {
+        &self.interface
+    }
+
+    /// Register a device, calling `callback` with its status byte whenever it requests service
+    ///
+    /// The device should be on the bus of the interface, and have its service request enable register configured.
+    pub fn register<F>(&self, device: Session, callback: F) -> SrqDeviceId
+    where
+        F: FnMut(StatusByte) + Send + 'static,
+    {
+        let mut devices = lock(&self.devices);
+        let id = SrqDeviceId(devices.next_id);
+        devices.next_id += 1;
+        devices.registered.push(Device {
+            id,
+            session: device,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Stop dispatching service requests to a device, returning its session
+    #[must_use]
+    pub fn unregister(&self, id: SrqDeviceId) -> Option<Session> {
+        let mut devices = lock(&self.devices);
+        let index = devices
+            .registered
+            .iter()
+            .position(|device| device.id == id)?;
+        Some(devices.registered.remove(index).session)
+    }
+
+    /// Returns true if the SRQ line of the bus is asserted
+    ///
+    /// # Errors
+    /// Will return an error if the line state cannot be read
+    pub fn srq_asserted(&self) -> Result<bool, Error> {
+        Ok(self.interface.get_attribute::<GpibSrqState>()? == State::Asserted)
+    }
+
+    /// Poll the registered devices now, without waiting for a service request event
+    ///
+    /// Returns the number of requests serviced.
+    ///
+    /// # Errors
+    /// Will return an error if a device cannot be polled, after servicing the others
+    pub fn poll(&self) -> Result<usize, Error> {
+        let mut devices = lock(&self.devices);
+        let serviced = service(&self.interface, &mut devices);
+        match devices.last_error.take() {
+            Some(e) => Err(e),
+            None => Ok(serviced),
+        }
+    }
+
+    /// Take the last error raised while servicing a request event, including panics in the callbacks
+    #[must_use]
+    pub fn take_error(&self) -> Option<Error> {
+        self.guard
+            .take_panic()
+            .or_else(|| lock(&self.devices).last_error.take())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ResourceManager, SessionOptions};
+
+    #[test]
+    fn test_poll_all() {
+        let serviced = Arc::new(Mutex::new(Vec::new()));
+        let mut devices = Devices::default();
+        for id in 0..3 {
+            let serviced = serviced.clone();
+            devices.registered.push(Device {
+                id: SrqDeviceId(id),
+                session: Session::null(),
+                callback: Box::new(move |status| serviced.lock().unwrap().push((id, status))),
+            });
+        }
+
+        // The second device requests service, the third cannot be polled
+        let mut polls = 0;
+        let count = devices.poll_all(|_| {
+            polls += 1;
+            match polls {
+                1 => Ok(StatusByte::MAV),
+                2 => Ok(StatusByte::RQS | StatusByte::ESB),
+                _ => Err(Error::from_msg("Polling failed")),
+            }
+        });
+
+        assert_eq!(count, 1);
+        assert_eq!(
+            *serviced.lock().unwrap(),
+            [(1, StatusByte::RQS | StatusByte::ESB)]
+        );
+        assert!(devices.last_error.is_some());
+    }
+
+    #[test]
+    fn test_srq_dispatcher() {
+        let rm = ResourceManager::new().unwrap();
+        let interface = std::env::var("LOCAL_GPIB_INTF").unwrap_or("GPIB0::INTFC".to_string());
+        let interface = Session::new(&rm, &interface, SessionOptions::default()).unwrap();
+
+        let dispatcher = SrqDispatcher::new(interface).unwrap();
+        let id = dispatcher.register(crate::get_local_device(), |_| {});
+        assert!(!dispatcher.srq_asserted().unwrap());
+        assert_eq!(dispatcher.poll().unwrap(), 0);
+        assert!(dispatcher.unregister(id).is_some());
+        assert!(dispatcher.take_error().is_none());
+    }
+}
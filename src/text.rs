@@ -0,0 +1,264 @@
+//! Text encoding and termination used by the string I/O helpers
+//!
+//! See `Session::set_text_options`, which also configures the matching VISA attributes
+//! (`TermChar`, `TermCharEn` and `SendEndEn`).
+use crate::{
+    attribute::misc::{SendEndEn, TermChar, TermCharEn},
+    error::{Error, ErrorType},
+    Session,
+};
+
+/// Character encoding used by `Session::read_string` and `Session::write_string`
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Encoding {
+    /// 7-bit ASCII; characters outside of the range are rejected
+    Ascii,
+
+    /// ISO 8859-1, where every byte is the code point of the same value
+    ///
+    /// Covers the degree sign (0xB0) and the micro sign (0xB5) emitted by many older instruments
+    Latin1,
+
+    /// UTF-8, replacing invalid sequences with U+FFFD
+    Utf8Lossy,
+
+    /// UTF-8, rejecting invalid sequences
+    #[default]
+    Utf8,
+
+    /// User provided conversion functions
+    Custom {
+        /// Convert a string to the bytes sent to the device
+        encode: fn(&str) -> Result<Vec<u8>, Error>,
+
+        /// Convert the bytes received from the device to a string
+        decode: fn(&[u8]) -> Result<String, Error>,
+    },
+}
+impl Encoding {
+    /// Convert a string to the bytes sent to the device
+    ///
+    /// # Errors
+    /// Will return an error if the string contains characters that cannot be represented
+    pub fn encode(&self, s: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Ascii => {
+                if s.is_ascii() {
+                    Ok(s.as_bytes().to_vec())
+                } else {
+                    Err(Self::unrepresentable("ASCII"))
+                }
+            }
+
+            Self::Latin1 => s
+                .chars()
+                .map(|c| u8::try_from(u32::from(c)).map_err(|_| Self::unrepresentable("Latin-1")))
+                .collect(),
+
+            Self::Utf8Lossy | Self::Utf8 => Ok(s.as_bytes().to_vec()),
+            Self::Custom { encode, .. } => encode(s),
+        }
+    }
+
+    /// Convert the bytes received from the device to a string
+    ///
+    /// # Errors
+    /// Will return an error if the bytes are not valid for the encoding
+    pub fn decode(&self, bytes: &[u8]) -> Result<String, Error> {
+        match self {
+            Self::Ascii => {
+                if bytes.is_ascii() {
+                    Ok(bytes.iter().map(|&b| char::from(b)).collect())
+                } else {
+                    Err(Error {
+                        status: ErrorType::InvParameter,
+                        description: Some("Invalid ASCII string".to_string()),
+                    })
+                }
+            }
+
+            Self::Latin1 => Ok(bytes.iter().map(|&b| char::from(b)).collect()),
+            Self::Utf8Lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            Self::Utf8 => Ok(std::str::from_utf8(bytes)?.to_string()),
+            Self::Custom { decode, .. } => decode(bytes),
+        }
+    }
+
+    fn unrepresentable(encoding: &str) -> Error {
+        Error {
+            status: ErrorType::InvParameter,
+            description: Some(format!("String cannot be represented in {encoding}")),
+        }
+    }
+}
+
+/// Per-session settings for the string I/O helpers
+///
+/// The defaults match the historical behaviour: a `\n` is appended to writes,
+/// reads are returned as-is, and the data must be valid UTF-8.
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    /// Appended to every string written by `Session::write_string`
+    ///
+    /// Leave empty for devices that rely on END alone, in which case `send_end` must be set
+    pub write_termination: String,
+
+    /// Stripped from the end of every string read by `Session::read_string`, if present
+    ///
+    /// When not empty, its last character is used as the VISA termination character
+    pub read_termination: String,
+
+    /// Whether END is asserted with the last byte of every write (`VI_ATTR_SEND_END_EN`)
+    pub send_end: bool,
+
+    /// Character encoding of the data
+    pub encoding: Encoding,
+}
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            write_termination: "\n".to_string(),
+            read_termination: String::new(),
+            send_end: true,
+            encoding: Encoding::default(),
+        }
+    }
+}
+impl TextOptions {
+    /// Build options matching the VISA attributes currently set on a session
+    ///
+    /// The read termination is the VISA termination character if `TermCharEn` is set; the encoding is UTF-8.
+    ///
+    /// # Errors
+    /// Will return an error if the attributes cannot be read
+    pub fn from_session(session: &Session) -> Result<Self, Error> {
+        let read_termination = if session.get_attribute::<TermCharEn>()? {
+            char::from(session.get_attribute::<TermChar>()?).to_string()
+        } else {
+            String::new()
+        };
+
+        Ok(Self {
+            write_termination: session.text().write_termination.clone(),
+            read_termination,
+            send_end: session.get_attribute::<SendEndEn>()?,
+            encoding: Encoding::default(),
+        })
+    }
+
+    /// Encode a string and append the write termination
+    ///
+    /// # Errors
+    /// Will return an error if the string cannot be encoded
+    pub fn encode_message(&self, s: &str) -> Result<Vec<u8>, Error> {
+        let mut bytes = self.encoding.encode(s)?;
+        bytes.extend(self.encoding.encode(&self.write_termination)?);
+        Ok(bytes)
+    }
+
+    /// Decode a message and strip the read termination
+    ///
+    /// # Errors
+    /// Will return an error if the data cannot be decoded
+    pub fn decode_message(&self, bytes: &[u8]) -> Result<String, Error> {
+        let mut s = self.encoding.decode(bytes)?;
+        if !self.read_termination.is_empty() && s.ends_with(&self.read_termination) {
+            s.truncate(s.len() - self.read_termination.len());
+        }
+        Ok(s)
+    }
+
+    /// The byte used as VISA termination character, if any
+    ///
+    /// # Errors
+    /// Will return an error if the read termination cannot be encoded
+    pub fn term_char(&self) -> Result<Option<u8>, Error> {
+        Ok(self
+            .encoding
+            .encode(&self.read_termination)?
+            .last()
+            .copied())
+    }
+}
+
+impl Session {
+    /// Get the text settings used by the string I/O helpers
+    ///
+    /// The settings are shared by all clones of the session.
+    #[must_use]
+    pub fn text_options(&self) -> TextOptions {
+        self.text().clone()
+    }
+
+    /// Set the text settings used by the string I/O helpers
+    ///
+    /// The VISA attributes are updated to agree with the settings:
+    /// - `TermChar` and `TermCharEn` are set from the read termination, so that reads stop at the terminator
+    /// - `SendEndEn` is set from `send_end`
+    ///
+    /// The settings apply to all clones of the session. Other threads cannot use the session while they change.
+    ///
+    /// # Errors
+    /// Will return an error if the settings are inconsistent, or the attributes cannot be set
+    pub fn set_text_options(&mut self, options: TextOptions) -> Result<(), Error> {
+        if options.write_termination.is_empty() && !options.send_end {
+            return Err(Error {
+                status: ErrorType::InvSetup,
+                description: Some(
+                    "Writes need either a termination or END to mark the end of a message"
+                        .to_string(),
+                ),
+            });
+        }
+
+        let _io = self.io_lock().acquire();
+        match options.term_char()? {
+            Some(term_char) => {
+                self.set_attribute::<TermChar>(term_char)?;
+                self.set_attribute::<TermCharEn>(true)?;
+            }
+            None => self.set_attribute::<TermCharEn>(false)?,
+        }
+        self.set_attribute::<SendEndEn>(options.send_end)?;
+
+        self.set_text(options);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encodings() {
+        assert_eq!(Encoding::Latin1.encode("25°C").unwrap(), b"25\xB0C");
+        assert_eq!(Encoding::Latin1.decode(b"25\xB0C").unwrap(), "25°C");
+        assert!(Encoding::Latin1.encode("€").is_err());
+
+        assert!(Encoding::Ascii.encode("25°C").is_err());
+        assert!(Encoding::Ascii.decode(b"25\xB0C").is_err());
+
+        assert!(Encoding::Utf8.decode(b"25\xB0C").is_err());
+        assert_eq!(
+            Encoding::Utf8Lossy.decode(b"25\xB0C").unwrap(),
+            "25\u{FFFD}C"
+        );
+    }
+
+    #[test]
+    fn test_termination() {
+        let options = TextOptions {
+            write_termination: "\r\n".to_string(),
+            read_termination: "\r\n".to_string(),
+            ..TextOptions::default()
+        };
+
+        assert_eq!(options.encode_message("*IDN?").unwrap(), b"*IDN?\r\n");
+        assert_eq!(options.decode_message(b"1.234\r\n").unwrap(), "1.234");
+        assert_eq!(options.decode_message(b"1.234").unwrap(), "1.234");
+        assert_eq!(options.term_char().unwrap(), Some(b'\n'));
+
+        assert_eq!(TextOptions::default().term_char().unwrap(), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! Atomic sequences of operations on a session
+//!
+//! Clones of a session can be used from several threads. `Session::transaction` gives a closure exclusive access to
+//! the session for a whole sequence of operations, so that another thread cannot write its own command between a
+//! query and its response:
+//! ```ignore
+//! let (voltage, current) = session.transaction(|tx| {
+//!     tx.write_string("CONF:VOLT:DC; :TRIG:SOUR IMM")?;
+//!     let voltage: f64 = tx.query("READ?")?;
+//!     let current: f64 = tx.query("MEAS:CURR?")?;
+//!     Ok((voltage, current))
+//! })?;
+//! ```
+//!
+//! `Session::query`, and the helpers built on it, run as transactions.
+//! Transactions only exclude the other threads of this process; `Session::transaction_locked` also holds an
+//! exclusive VISA lock, to exclude other processes.
+
+use crate::{error::Error, ExclusiveLockGuard, Session};
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread::ThreadId,
+    time::Duration,
+};
+
+/// The in-process lock on the I/O of a session, shared by its clones
+///
+/// The thread holding the lock can take it again, so that transactions and `Session::with_lock` can nest.
+#[derive(Debug, Default)]
+pub(crate) struct IoLock {
+    /// The holder of the lock, and the number of times it took it
+    owner: Mutex<Option<(Holder, usize)>>,
+    released: Condvar,
+}
+
+/// The holder of an `IoLock`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Holder {
+    /// A thread, which can take the lock again
+    Thread(ThreadId),
+    /// An async task, which can resume on any thread
+    Task,
+}
+
+impl IoLock {
+    fn owner(&self) -> MutexGuard<'_, Option<(Holder, usize)>> {
+        self.owner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait until the lock is free or already held by `holder`, then take it
+    fn take(&self, holder: Holder) {
+        let mut owner = self.owner();
+        loop {
+            match &mut *owner {
+                None => *owner = Some((holder, 1)),
+                Some((current, depth)) if *current == holder && holder != Holder::Task => {
+                    *depth += 1;
+                }
+                Some(_) => {
+                    owner = self
+                        .released
+                        .wait(owner)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    continue;
+                }
+            }
+            return;
+        }
+    }
+
+    fn release(&self) {
+        let mut owner = self.owner();
+        if let Some((_, depth)) = &mut *owner {
+            *depth -= 1;
+            if *depth == 0 {
+                *owner = None;
+                self.released.notify_one();
+            }
+        }
+    }
+
+    /// Take the lock, waiting for other threads to release it
+    pub(crate) fn acquire(self: &Arc<Self>) -> IoGuard {
+        self.take(Holder::Thread(std::thread::current().id()));
+        IoGuard {
+            lock: self.clone(),
+            _thread_bound: PhantomData,
+        }
+    }
+
+    /// Take the lock on behalf of an async task, blocking until it is free
+    ///
+    /// The guard can be held across `.await`. It is not reentrant: the task must not take the lock again.
+    #[cfg(any(feature = "tokio", feature = "futures"))]
+    pub(crate) fn acquire_for_task(self: &Arc<Self>) -> TaskIoGuard {
+        self.take(Holder::Task);
+        TaskIoGuard { lock: self.clone() }
+    }
+}
+
+/// A hold on an `IoLock`, released when dropped by the thread that took it
+#[derive(Debug)]
+pub(crate) struct IoGuard {
+    lock: Arc<IoLock>,
+    _thread_bound: PhantomData<*const ()>,
+}
+impl Drop for IoGuard {
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+/// A hold on an `IoLock` by an async task, released when dropped
+#[cfg(any(feature = "tokio", feature = "futures"))]
+#[derive(Debug)]
+pub(crate) struct TaskIoGuard {
+    lock: Arc<IoLock>,
+}
+#[cfg(any(feature = "tokio", feature = "futures"))]
+impl Drop for TaskIoGuard {
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+/// Exclusive access to a session for the duration of `Session::transaction`
+///
+/// Dereferences to the session, so that all its operations are available.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    session: &'a mut Session,
+
+    // The VISA lock is released before the in-process lock
+    _visa_lock: Option<ExclusiveLockGuard>,
+    _io: IoGuard,
+}
+impl Deref for Transaction<'_> {
+    type Target = Session;
+    fn deref(&self) -> &Session {
+        self.session
+    }
+}
+impl DerefMut for Transaction<'_> {
+    fn deref_mut(&mut self) -> &mut Session {
+        self.session
+    }
+}
+
+impl Session {
+    /// Run a sequence of operations without other threads using the session in between
+    ///
+    /// Transactions nest: a thread already inside a transaction on the session can start another one.
+    ///
+    /// # Errors
+    /// Returns the error of the closure
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<T, Error>,
+    {
+        let io = self.io_lock().acquire();
+        f(&mut Transaction {
+            session: self,
+            _visa_lock: None,
+            _io: io,
+        })
+    }
+
+    /// Run a sequence of operations while holding an exclusive VISA lock on the resource
+    ///
+    /// Like `Session::transaction`, but also excludes other sessions and processes.
+    ///
+    /// # Errors
+    /// Will return an error if the lock cannot be acquired within `lock_timeout`, or the error of the closure
+    pub fn transaction_locked<T, F>(&mut self, lock_timeout: Duration, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> Result<T, Error>,
+    {
+        let io = self.io_lock().acquire();
+        let visa_lock = self.lock_exclusive(lock_timeout)?;
+        f(&mut Transaction {
+            session: self,
+            _visa_lock: Some(visa_lock),
+            _io: io,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::get_local_device;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_io_lock() {
+        let lock = Arc::new(IoLock::default());
+        let inside = Arc::new(AtomicUsize::new(0));
+
+        // The lock can be taken again by the thread holding it
+        let outer = lock.acquire();
+        let inner = lock.acquire();
+        drop(inner);
+
+        let thread = {
+            let lock = lock.clone();
+            let inside = inside.clone();
+            std::thread::spawn(move || {
+                let _guard = lock.acquire();
+                inside.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(inside.load(Ordering::SeqCst), 0);
+
+        drop(outer);
+        thread.join().unwrap();
+        assert_eq!(inside.load(Ordering::SeqCst), 1);
+        assert!(lock.owner().is_none());
+    }
+
+    #[test]
+    #[cfg(any(feature = "tokio", feature = "futures"))]
+    fn test_task_io_lock() {
+        let lock = Arc::new(IoLock::default());
+
+        // A task guard excludes threads, and can be released from another thread
+        let guard = lock.acquire_for_task();
+        let thread = {
+            let lock = lock.clone();
+            std::thread::spawn(move || drop(lock.acquire()))
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!thread.is_finished());
+
+        std::thread::spawn(move || drop(guard)).join().unwrap();
+        thread.join().unwrap();
+        assert!(lock.owner().is_none());
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut session = get_local_device();
+        let idn = session
+            .transaction(|tx| {
+                tx.write_string("*IDN?")?;
+                let idn = tx.read_string()?;
+
+                // Nested helpers take the lock again
+                assert_eq!(tx.idn()?, idn);
+                Ok(idn)
+            })
+            .unwrap();
+        assert!(!idn.is_empty());
+
+        let idn = session
+            .transaction_locked(Duration::from_secs(1), |tx| tx.idn())
+            .unwrap();
+        assert!(!idn.is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+//! USB interrupt packets, and USBTMC/USB488 notifications
+//!
+//! `Session::usb_interrupts` enables `Event::UsbIntr` on a USB RAW session, and returns a `UsbInterrupts` queue
+//! yielding the data of each interrupt-IN packet, whatever its size:
+//! ```ignore
+//! let mut interrupts = session.usb_interrupts()?;
+//! if let Some(Usb488Notification::ServiceRequest(status)) = interrupts.next_notification(timeout)? {
+//!     // ...
+//! }
+//! ```
+//!
+//! USB488 devices report service requests and the replies to `READ_STATUS_BYTE` requests on the interrupt-IN
+//! endpoint; `Usb488Notification` decodes these packets. USB INSTR sessions decode them already, and report service
+//! requests as `Event::ServiceReq` instead.
+
+use crate::{
+    attribute::usb::UsbMaxIntrSize, error::Error, event::Event, ieee4882::StatusByte, EventQueue,
+    Session,
+};
+use std::time::Duration;
+
+/// Default maximum interrupt packet size, the largest full-speed interrupt packet
+const DEFAULT_MAX_SIZE: u16 = 64;
+
+/// A notification sent by a USB488 device on its interrupt-IN endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Usb488Notification {
+    /// The device requested service, and sent its status byte
+    ServiceRequest(StatusByte),
+
+    /// The reply to a `READ_STATUS_BYTE` control request, matched to it by tag
+    StatusByte {
+        /// The tag of the request, between 2 and 127
+        tag: u8,
+
+        /// The status byte of the device
+        status: StatusByte,
+    },
+
+    /// A vendor-specific or reserved notification, kept as received
+    Other(Vec<u8>),
+}
+impl Usb488Notification {
+    /// `bNotify1` of a service request notification
+    const SRQ: u8 = 0x81;
+
+    /// Bit of `bNotify1` set by USB488 notifications, the other bits holding the tag
+    const USB488: u8 = 0x80;
+}
+impl TryFrom<&[u8]> for Usb488Notification {
+    type Error = Error;
+    fn try_from(packet: &[u8]) -> Result<Self, Self::Error> {
+        let [notify1, notify2, ..] = *packet else {
+            return Err(Error::from_msg("USB488 notification too short"));
+        };
+
+        let status = StatusByte::from_bits(notify2);
+        Ok(match notify1 {
+            Self::SRQ => Self::ServiceRequest(status),
+            _ if notify1 & Self::USB488 != 0 => Self::StatusByte {
+                tag: notify1 & !Self::USB488,
+                status,
+            },
+            _ => Self::Other(packet.to_vec()),
+        })
+    }
+}
+
+/// A queue of the interrupt packets received by a USB session
+///
+/// Iterating over the queue blocks until the next packet arrives, and never ends on its own.
+/// Dropping the queue disables `Event::UsbIntr`.
+#[derive(Debug)]
+#[must_use = "Dropping the queue disables USB interrupt events"]
+pub struct UsbInterrupts {
+    queue: EventQueue,
+}
+impl UsbInterrupts {
+    /// The underlying event queue, counting the interrupts lost while it was full
+    pub fn queue(&self) -> &EventQueue {
+        &self.queue
+    }
+
+    /// Wait up to `timeout` for the next interrupt packet
+    ///
+    /// Returns `None` if no interrupt arrived in time.
+    /// Data beyond `UsbMaxIntrSize` bytes is lost by VISA.
+    ///
+    /// # Errors
+    /// Will return an error if the interrupt cannot be waited on, or its data cannot be read
+    pub fn next_packet(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, Error> {
+        self.queue
+            .try_next(timeout)?
+            .map(|event| event.usb_interrupt_data())
+            .transpose()
+    }
+
+    /// Wait up to `timeout` for the next interrupt packet, decoded as a USB488 notification
+    ///
+    /// # Errors
+    /// Will return an error if the interrupt cannot be waited on, or is too short to be a notification
+    pub fn next_notification(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<Usb488Notification>, Error> {
+        self.next_packet(timeout)?
+            .map(|packet| Usb488Notification::try_from(packet.as_slice()))
+            .transpose()
+    }
+}
+
+impl Iterator for UsbInterrupts {
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let packet = self.queue.next()?;
+        Some(packet.and_then(|event| event.usb_interrupt_data()))
+    }
+}
+
+impl Session {
+    /// Receive the interrupt packets of a USB device, of up to 64 bytes each
+    ///
+    /// See `Session::usb_interrupts_with_size`
+    ///
+    /// # Errors
+    /// Will return an error if the packet size cannot be set, or the event cannot be enabled
+    pub fn usb_interrupts(&self) -> Result<UsbInterrupts, Error> {
+        self.usb_interrupts_with_size(DEFAULT_MAX_SIZE)
+    }
+
+    /// Receive the interrupt packets of a USB device, of up to `max_size` bytes each
+    ///
+    /// `UsbMaxIntrSize` is set before the event is enabled, as it is read-only while USB interrupts are enabled.
+    ///
+    /// # Errors
+    /// Will return an error if the packet size cannot be set, or the event cannot be enabled
+    pub fn usb_interrupts_with_size(&self, max_size: u16) -> Result<UsbInterrupts, Error> {
+        let mut session = self.clone();
+        session.set_attribute::<UsbMaxIntrSize>(max_size)?;
+        Ok(UsbInterrupts {
+            queue: EventQueue::enable(session, Event::UsbIntr)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ResourceManager, SessionOptions};
+
+    #[test]
+    fn test_usb488_notification() {
+        let srq = Usb488Notification::try_from([0x81, 0x50].as_slice()).unwrap();
+        assert_eq!(
+            srq,
+            Usb488Notification::ServiceRequest(StatusByte::RQS | StatusByte::MAV)
+        );
+
+        let reply = Usb488Notification::try_from([0x85, 0x10].as_slice()).unwrap();
+        assert_eq!(
+            reply,
+            Usb488Notification::StatusByte {
+                tag: 5,
+                status: StatusByte::MAV
+            }
+        );
+
+        let other = Usb488Notification::try_from([0x01, 0x02, 0x03].as_slice()).unwrap();
+        assert_eq!(other, Usb488Notification::Other(vec![1, 2, 3]));
+
+        assert!(Usb488Notification::try_from([0x81].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_usb_interrupts() {
+        let rm = ResourceManager::new().unwrap();
+        let resource = std::env::var("LOCAL_USB_RAW").unwrap_or_else(|_| {
+            let mut search = rm.search("USB?*RAW").unwrap();
+            search.next().unwrap().unwrap().interface().to_string()
+        });
+        let session = Session::new(&rm, &resource, SessionOptions::default()).unwrap();
+
+        let mut interrupts = session.usb_interrupts_with_size(16).unwrap();
+        assert_eq!(session.get_attribute::<UsbMaxIntrSize>().unwrap(), 16);
+        assert!(interrupts.next_packet(Duration::ZERO).unwrap().is_none());
+        assert_eq!(interrupts.queue().overflows(), 0);
+    }
+}
@@ -0,0 +1,300 @@
+//! Memory-mapped windows on register-based devices
+//!
+//! `Session::map_window` maps a region of an address space into the process with `viMapAddress`, and returns a
+//! `MappedWindow` accessing it with `viPeekXX` and `viPokeXX`. These skip the checks VISA makes on every
+//! `Session::peek_reg` and `Session::poke_reg`, which makes them faster for repeated accesses to the same region:
+//! ```ignore
+//! let window = session.map_window(AddressSpace::A16, 0xC000, 0x40)?;
+//! let id: u16 = window.peek(0x00)?;
+//! window.poke(0x04, 0x0001u16)?;
+//! ```
+//!
+//! A session maps one window at a time, unmapped when the `MappedWindow` is dropped.
+//! `WinAccessPriv` and `WinByteOrder` are read-only while a window is mapped; `Session::map_window_with` sets them
+//! before mapping.
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "Needed for compatibility with the VISA library"
+)]
+
+use crate::{
+    attribute::misc::{
+        AccessPrivilege, ByteOrder, WinAccess, WinAccessPriv, WinByteOrder, WindowAccess,
+    },
+    bindings,
+    error::Error,
+    register::check_alignment,
+    AddressSpace, RegisterValue, Session,
+};
+
+/// Options for mapping a window
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WindowOptions {
+    /// The address modifier of the accesses through the window, or `None` to keep the current `WinAccessPriv`
+    pub access_priv: Option<AccessPrivilege>,
+
+    /// The byte order of the accesses through the window, or `None` to keep the current `WinByteOrder`
+    pub byte_order: Option<ByteOrder>,
+}
+
+/// A region of an address space mapped into the process, unmapped when dropped
+#[derive(Debug)]
+#[must_use = "Dropping the window unmaps it"]
+pub struct MappedWindow {
+    session: Session,
+    space: AddressSpace,
+    offset: u64,
+    size: usize,
+
+    // The local address of the window, kept as an integer so that the window can be sent to other threads
+    address: usize,
+    mapped: bool,
+}
+impl MappedWindow {
+    /// The session the window is mapped on
+    #[must_use]
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// The address space of the window
+    #[must_use]
+    pub fn space(&self) -> AddressSpace {
+        self.space
+    }
+
+    /// The offset of the start of the window in its address space
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The size of the window, in bytes
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The local address of the window, as returned by `viMapAddress`
+    ///
+    /// The address can only be dereferenced if `MappedWindow::access` returns `WindowAccess::Dereference`.
+    #[must_use]
+    pub fn address(&self) -> bindings::ViAddr {
+        self.address as bindings::ViAddr
+    }
+
+    /// How the window can be accessed (`VI_ATTR_WIN_ACCESS`)
+    ///
+    /// # Errors
+    /// Will return an error if the attribute cannot be read
+    pub fn access(&self) -> Result<WindowAccess, Error> {
+        self.session.get_attribute::<WinAccess>()
+    }
+
+    /// Read a register of the window with `viPeekXX`
+    ///
+    /// `offset` is relative to the start of the window.
+    ///
+    /// # Errors
+    /// Will return an error if the register is not aligned to the width of `T`, or is not entirely inside the window
+    pub fn peek<T: RegisterValue>(&self, offset: usize) -> Result<T, Error> {
+        let address = self.address_of::<T>(offset)?;
+        let mut value = T::default();
+        unsafe { T::vi_peek(self.session.session_id(), address, &raw mut value) };
+        Ok(value)
+    }
+
+    /// Write a register of the window with `viPokeXX`
+    ///
+    /// `offset` is relative to the start of the window.
+    ///
+    /// # Errors
+    /// Will return an error if the register is not aligned to the width of `T`, or is not entirely inside the window
+    pub fn poke<T: RegisterValue>(&self, offset: usize, value: T) -> Result<(), Error> {
+        let address = self.address_of::<T>(offset)?;
+        unsafe { T::vi_poke(self.session.session_id(), address, value) };
+        Ok(())
+    }
+
+    /// Unmap the window, reporting any error
+    ///
+    /// # Errors
+    /// Will return an error if the window cannot be unmapped
+    pub fn unmap(mut self) -> Result<(), Error> {
+        self.release()
+    }
+
+    /// The local address of a register of type `T` at `offset` in the window
+    fn address_of<T: RegisterValue>(&self, offset: usize) -> Result<bindings::ViAddr, Error> {
+        let end = offset.checked_add(usize::from(T::WIDTH));
+        if end.is_none_or(|end| end > self.size) {
+            return Err(Error::new(bindings::VI_ERROR_INV_OFFSET, None));
+        }
+        check_alignment::<T>(self.offset + offset as u64)?;
+        Ok((self.address + offset) as bindings::ViAddr)
+    }
+
+    fn release(&mut self) -> Result<(), Error> {
+        if !self.mapped {
+            return Ok(());
+        }
+        self.mapped = false;
+
+        let vi = self.session.session_id();
+        Error::wrap_binding(Some(vi), || unsafe { bindings::viUnmapAddress(vi) })
+    }
+}
+impl Drop for MappedWindow {
+    fn drop(&mut self) {
+        self.release().ok();
+    }
+}
+
+impl Session {
+    /// Map `size` bytes of an address space, starting at `offset`, into the process
+    ///
+    /// The window is accessed with the current `WinAccessPriv` and `WinByteOrder` of the session.
+    /// Offsets beyond 32 bits use `viMapAddressEx`.
+    ///
+    /// # Errors
+    /// Will return an error if the session already has a mapped window, or the region cannot be mapped
+    pub fn map_window(
+        &self,
+        space: AddressSpace,
+        offset: u64,
+        size: usize,
+    ) -> Result<MappedWindow, Error> {
+        self.map_window_with(space, offset, size, WindowOptions::default())
+    }
+
+    /// Map `size` bytes of an address space, starting at `offset`, into the process
+    ///
+    /// `WinAccessPriv` and `WinByteOrder` are set from `options` before mapping, as they are read-only while the
+    /// window is mapped.
+    ///
+    /// # Errors
+    /// Will return an error if the attributes cannot be set, the session already has a mapped window, or the region
+    /// cannot be mapped
+    pub fn map_window_with(
+        &self,
+        space: AddressSpace,
+        offset: u64,
+        size: usize,
+        options: WindowOptions,
+    ) -> Result<MappedWindow, Error> {
+        let mut session = self.clone();
+        if let Some(access_priv) = options.access_priv {
+            session.set_attribute::<WinAccessPriv>(access_priv)?;
+        }
+        if let Some(byte_order) = options.byte_order {
+            session.set_attribute::<WinByteOrder>(byte_order)?;
+        }
+
+        let map_size = bindings::ViBusSize::try_from(size)
+            .map_err(|_| Error::new(bindings::VI_ERROR_INV_SIZE, None))?;
+        let vi = self.session_id();
+        let mut address: bindings::ViAddr = std::ptr::null_mut();
+        Error::wrap_binding(Some(vi), || unsafe {
+            let (access, suggested) = (
+                bindings::VI_FALSE as bindings::ViBoolean,
+                std::ptr::null_mut(),
+            );
+            match bindings::ViBusAddress::try_from(offset) {
+                Ok(map_offset) => bindings::viMapAddress(
+                    vi,
+                    space as u16,
+                    map_offset,
+                    map_size,
+                    access,
+                    suggested,
+                    &raw mut address,
+                ),
+                Err(_) => bindings::viMapAddressEx(
+                    vi,
+                    space as u16,
+                    offset,
+                    map_size,
+                    access,
+                    suggested,
+                    &raw mut address,
+                ),
+            }
+        })?;
+
+        Ok(MappedWindow {
+            session,
+            space,
+            offset,
+            size,
+            address: address as usize,
+            mapped: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        attribute::misc::{WinBaseAddr, WinSize},
+        error::ErrorType,
+        register::test::get_register_device,
+    };
+
+    #[test]
+    fn test_address_of() {
+        let window = MappedWindow {
+            session: Session::null(),
+            space: AddressSpace::A16,
+            offset: 0xC000,
+            size: 0x40,
+            address: 0x1000,
+            mapped: false,
+        };
+
+        assert_eq!(window.address_of::<u16>(0x3E).unwrap() as usize, 0x103E);
+        assert_eq!(window.address_of::<u64>(0x00).unwrap() as usize, 0x1000);
+
+        let error = window.address_of::<u32>(0x3E).unwrap_err();
+        assert_eq!(error.status, ErrorType::InvOffset);
+        assert!(window.address_of::<u8>(0x40).is_err());
+        assert!(window.address_of::<u8>(usize::MAX).is_err());
+
+        let error = window.address_of::<u32>(0x02).unwrap_err();
+        assert_eq!(error.status, ErrorType::NsupAlignOffset);
+    }
+
+    #[test]
+    fn test_map_window() {
+        let session = get_register_device();
+        let options = WindowOptions {
+            access_priv: Some(AccessPrivilege::DataPriv),
+            byte_order: Some(ByteOrder::LittleEndian),
+        };
+        let window = session
+            .map_window_with(AddressSpace::PxiConfig, 0, 0x40, options)
+            .unwrap();
+        assert_ne!(window.access().unwrap(), WindowAccess::NotMapped);
+        assert_eq!(session.get_attribute::<WinBaseAddr>().unwrap(), 0);
+        assert_eq!(session.get_attribute::<WinSize>().unwrap(), 0x40);
+
+        let vendor: u16 = window.peek(0x00).unwrap();
+        assert_eq!(
+            vendor,
+            session
+                .peek_reg::<u16>(AddressSpace::PxiConfig, 0x00)
+                .unwrap()
+        );
+
+        // Only one window can be mapped per session
+        assert!(session
+            .map_window(AddressSpace::PxiConfig, 0, 0x40)
+            .is_err());
+
+        window.unmap().unwrap();
+        assert_eq!(
+            session.get_attribute::<WinAccess>().unwrap(),
+            WindowAccess::NotMapped
+        );
+    }
+}